@@ -0,0 +1,334 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use clap::ArgEnum;
+
+use crate::{
+    asg::Assignment,
+    kenken::{Field, KenKen},
+    search::SearchSolver,
+    solve::Outcome,
+};
+
+/// How much of the puzzle is taken into account for the candidates of a field.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, ArgEnum)]
+pub enum Stage {
+    /// Each area on its own.
+    Cage,
+    /// Areas and houses narrowing each other down, as at a node of the search.
+    Propagation,
+    /// The values of the field in any solution.
+    Search,
+}
+
+/// The candidates of every field after each stage, fields in reading order.
+/// A field with a single candidate is forced by that stage.
+#[derive(Debug)]
+pub struct Analysis {
+    pub cage: Vec<Vec<i64>>,
+    pub propagation: Vec<Vec<i64>>,
+    pub search: Vec<Vec<i64>>,
+    /// The number of possible assignments of every area, in the order of
+    /// `KenKen::areas`.
+    pub assignments: Vec<usize>,
+    /// Whether every search check finished; candidates of timed out checks
+    /// are kept, so there may be fewer.
+    pub exact: bool,
+}
+
+impl Analysis {
+    pub fn candidates(&self, stage: Stage) -> &[Vec<i64>] {
+        match stage {
+            Stage::Cage => &self.cage,
+            Stage::Propagation => &self.propagation,
+            Stage::Search => &self.search,
+        }
+    }
+
+    pub fn counts(&self, stage: Stage) -> Vec<usize> {
+        self.candidates(stage).iter().map(|c| c.len()).collect()
+    }
+
+    /// The number of fields first forced by `stage`.
+    pub fn forced(&self, stage: Stage) -> usize {
+        let before = match stage {
+            Stage::Cage => None,
+            Stage::Propagation => Some(&self.cage),
+            Stage::Search => Some(&self.propagation),
+        };
+        (0..self.cage.len())
+            .filter(|&c| {
+                self.candidates(stage)[c].len() == 1 && before.is_none_or(|b| b[c].len() > 1)
+            })
+            .count()
+    }
+}
+
+/// Analyzes `kenken`. The search stage starts from a solution and then, for
+/// every field, searches for solutions with a value at the field not seen in
+/// any solution so far, until there is none; each search takes at most
+/// `timeout`. Fails if a field is in no area, as no solution assigns it.
+pub fn analyze(kenken: &KenKen, timeout: Option<Duration>) -> Result<Analysis> {
+    let size = kenken.size as usize;
+    let mut covered = vec![false; size * size];
+    for f in kenken.areas.iter().flat_map(|area| &area.fields) {
+        covered[cell(kenken, f)] = true;
+    }
+    if let Some(c) = covered.iter().position(|&c| !c) {
+        bail!("field ({},{}) is in no area", c % size, c / size);
+    }
+    let options: Vec<Vec<Assignment>> = kenken
+        .areas
+        .iter()
+        .map(|area| kenken.possible_assignments(area))
+        .collect();
+    let assignments = options.iter().map(|o| o.len()).collect();
+    let cage = domains(kenken, &options);
+    let (propagation, search, exact) = match propagate(kenken, options) {
+        Some(options) => {
+            let propagation = domains(kenken, &options);
+            let (search, exact) = backbone(kenken, options, &propagation, timeout)?;
+            (propagation, search, exact)
+        }
+        None => (vec![vec![]; size * size], vec![vec![]; size * size], true),
+    };
+
+    Ok(Analysis {
+        cage,
+        propagation,
+        search,
+        assignments,
+        exact,
+    })
+}
+
+/// The values of every field in solutions among `options`, and whether no
+/// search timed out. The fields of a timed out search keep their candidates.
+/// Once the values of a field are known, the later searches only choose from
+/// options with these values there.
+fn backbone(
+    kenken: &KenKen,
+    mut options: Vec<Vec<Assignment>>,
+    candidates: &[Vec<i64>],
+    timeout: Option<Duration>,
+) -> Result<(Vec<Vec<i64>>, bool)> {
+    let solver = SearchSolver { timeout };
+    let mut seen = vec![vec![]; candidates.len()];
+    let mark = |seen: &mut Vec<Vec<i64>>, sol: &[Assignment]| {
+        for (f, v) in sol.iter().flat_map(|asg| asg.iter()) {
+            let c = cell(kenken, &f);
+            if !seen[c].contains(&v) {
+                seen[c].push(v);
+            }
+        }
+    };
+    match solver.solutions_among(kenken, &options, 1)?.0 {
+        Outcome::Solved(found) => mark(&mut seen, &found[0]),
+        Outcome::Infeasible => return Ok((seen, true)),
+        Outcome::TimedOut => return Ok((candidates.to_vec(), false)),
+    }
+
+    let size = kenken.size as usize;
+    let mut exact = true;
+    for c in 0..candidates.len() {
+        let field = Field((c % size) as u16, (c / size) as u16);
+        while candidates[c].iter().any(|v| !seen[c].contains(v)) {
+            let others: Vec<Vec<Assignment>> = options
+                .iter()
+                .map(|o| {
+                    o.iter()
+                        .filter(|asg| asg.get(&field).is_none_or(|v| !seen[c].contains(&v)))
+                        .cloned()
+                        .collect()
+                })
+                .collect();
+            match solver.solutions_among(kenken, &others, 1)?.0 {
+                Outcome::Solved(found) => mark(&mut seen, &found[0]),
+                Outcome::Infeasible => break,
+                Outcome::TimedOut => {
+                    exact = false;
+                    seen[c] = candidates[c].clone();
+                }
+            }
+        }
+        for o in &mut options {
+            o.retain(|asg| asg.get(&field).is_none_or(|v| seen[c].contains(&v)));
+        }
+    }
+    let values = kenken.values();
+    for domain in &mut seen {
+        domain.sort_by_key(|v| values.iter().position(|w| w == v));
+    }
+    Ok((seen, exact))
+}
+
+fn cell(kenken: &KenKen, field: &Field) -> usize {
+    field.1 as usize * kenken.size as usize + field.0 as usize
+}
+
+/// The values the options give each field, in the order of `KenKen::values`.
+/// Fields outside of all areas keep every value, or their given.
+fn domains(kenken: &KenKen, options: &[Vec<Assignment>]) -> Vec<Vec<i64>> {
+    let size = kenken.size as usize;
+    let mut domains = vec![kenken.values(); size * size];
+    for area in &kenken.areas {
+        for f in &area.fields {
+            domains[cell(kenken, f)].clear();
+        }
+    }
+    for (f, v) in &kenken.givens {
+        domains[cell(kenken, f)] = vec![*v];
+    }
+    for (f, v) in options.iter().flatten().flat_map(|asg| asg.iter()) {
+        let domain = &mut domains[cell(kenken, &f)];
+        if !domain.contains(&v) {
+            domain.push(v);
+        }
+    }
+    let values = kenken.values();
+    for domain in &mut domains {
+        domain.sort_by_key(|v| values.iter().position(|w| w == v));
+    }
+    domains
+}
+
+/// Narrows the options of the areas down until nothing changes: an option is
+/// dropped if it puts a value next to a field of another area forced to the
+/// same value in a house, and if only one area can place a value missing from
+/// a house, its options must place it there. `None` on a dead end.
+fn propagate(kenken: &KenKen, mut options: Vec<Vec<Assignment>>) -> Option<Vec<Vec<Assignment>>> {
+    let houses = kenken.houses();
+    let values = kenken.values();
+    let size = kenken.size as usize;
+    let mut area_of = vec![None; size * size];
+    for (a, area) in kenken.areas.iter().enumerate() {
+        for f in &area.fields {
+            area_of[cell(kenken, f)] = Some(a);
+        }
+    }
+
+    loop {
+        if options.iter().any(|o| o.is_empty()) {
+            return None;
+        }
+        let domains = domains(kenken, &options);
+        let forced = |f: &Field, v: i64, area: usize| {
+            (0..domains.len()).any(|c| {
+                let g = Field((c % size) as u16, (c / size) as u16);
+                area_of[c] != Some(area) && domains[c] == [v] && g != *f && kenken.same_house(f, &g)
+            })
+        };
+        let mut changed = false;
+        for (a, options) in options.iter_mut().enumerate() {
+            let before = options.len();
+            options.retain(|asg| asg.iter().all(|(f, v)| !forced(&f, v, a)));
+            changed |= options.len() < before;
+        }
+
+        for house in &houses {
+            for &v in &values {
+                let mut owners = house
+                    .iter()
+                    .filter(|f| domains[cell(kenken, f)].contains(&v))
+                    .map(|f| area_of[cell(kenken, f)]);
+                let owner = match owners.next()? {
+                    Some(a) if owners.all(|o| o == Some(a)) => a,
+                    _ => continue,
+                };
+                let before = options[owner].len();
+                options[owner].retain(|asg| asg.iter().any(|(f, w)| w == v && house.contains(&f)));
+                changed |= options[owner].len() < before;
+            }
+        }
+        if !changed {
+            return Some(options);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_analysis {
+    use super::*;
+    use crate::solve::Solver;
+
+    #[test]
+    fn test_analyze() {
+        let kenken: KenKen = ron::from_str(include_str!("../old/kenken43208.ron")).unwrap();
+        let analysis = analyze(&kenken, None).unwrap();
+        assert!(analysis.exact);
+        let fields = (kenken.size * kenken.size) as usize;
+        // each stage keeps a part of the candidates of the one before
+        for c in 0..fields {
+            assert!(analysis.propagation[c]
+                .iter()
+                .all(|v| analysis.cage[c].contains(v)));
+            assert!(analysis.search[c]
+                .iter()
+                .all(|v| analysis.propagation[c].contains(v)));
+        }
+        // the solution is unique, so search forces every field
+        assert!(analysis.counts(Stage::Search).iter().all(|&n| n == 1));
+        let forced: usize = [Stage::Cage, Stage::Propagation, Stage::Search]
+            .iter()
+            .map(|&s| analysis.forced(s))
+            .sum();
+        assert_eq!(forced, fields);
+        let (Outcome::Solved(sol), _) = crate::solve::Backend::Sat
+            .solver(None)
+            .solve(&kenken)
+            .unwrap()
+        else {
+            panic!("no solution");
+        };
+        for (f, v) in sol.iter().flat_map(|asg| asg.iter()) {
+            assert_eq!(analysis.search[cell(&kenken, &f)], [v]);
+        }
+        assert_eq!(
+            analysis.assignments.iter().sum::<usize>(),
+            kenken.total_number_of_assignments()
+        );
+    }
+
+    #[test]
+    fn test_several_solutions() {
+        use crate::kenken::{Area, Type};
+        // sums of horizontal pairs of the cyclic square of size 4
+        let areas = (0..4u16)
+            .flat_map(|y| {
+                [0, 2].map(|x| {
+                    let sum = (x + y) % 4 + 1 + (x + 1 + y) % 4 + 1;
+                    Area::new(Type::Add, sum as i64, vec![Field(x, y), Field(x + 1, y)])
+                })
+            })
+            .collect();
+        let kenken = KenKen::new(0, 4, areas);
+        let (Outcome::Solved(solutions), _) = SearchSolver { timeout: None }
+            .solutions(&kenken, 1000)
+            .unwrap()
+        else {
+            panic!("no solution");
+        };
+        assert!(solutions.len() > 1);
+        let mut values = vec![vec![]; 16];
+        for (f, v) in solutions.iter().flatten().flat_map(|asg| asg.iter()) {
+            if !values[cell(&kenken, &f)].contains(&v) {
+                values[cell(&kenken, &f)].push(v);
+            }
+        }
+        values.iter_mut().for_each(|v| v.sort());
+        let analysis = analyze(&kenken, None).unwrap();
+        assert_eq!(analysis.search, values);
+    }
+
+    #[test]
+    fn test_uncovered_field() {
+        use crate::kenken::{Area, Type};
+        // (1,1) is in no area
+        let areas = vec![
+            Area::new(Type::Single, 1, vec![Field(0, 0)]),
+            Area::new(Type::Single, 2, vec![Field(1, 0)]),
+            Area::new(Type::Single, 2, vec![Field(0, 1)]),
+        ];
+        assert!(analyze(&KenKen::new(0, 2, areas), None).is_err());
+    }
+}
@@ -1,10 +1,13 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
 
-use crate::kenken::{Area, Field, Type};
+use crate::kenken::{Area, Field, KenKen, Type};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Assignment {
-    values: HashMap<Field, u16>,
+    values: HashMap<Field, i64>,
 }
 
 impl Assignment {
@@ -14,23 +17,38 @@ impl Assignment {
         }
     }
 
-    pub fn set(&mut self, field: Field, value: u16) {
+    pub fn set(&mut self, field: Field, value: i64) {
         self.values.insert(field, value);
     }
 
-    pub fn get(&self, field: &Field) -> Option<u16> {
+    pub fn get(&self, field: &Field) -> Option<i64> {
         self.values.get(field).cloned()
     }
 
-    pub fn conflict(&self, field: &Field, value: u16) -> bool {
+    pub fn iter(&self) -> impl Iterator<Item = (Field, i64)> + '_ {
+        self.values.iter().map(|(f, v)| (*f, *v))
+    }
+
+    pub fn restrict(&self, fields: &[Field]) -> Assignment {
+        Assignment {
+            values: fields
+                .iter()
+                .filter_map(|f| self.get(f).map(|v| (*f, v)))
+                .collect(),
+        }
+    }
+
+    fn sorted_values(&self) -> Vec<(Field, i64)> {
+        let mut values: Vec<(Field, i64)> = self.iter().collect();
+        values.sort();
+        values
+    }
+
+    pub fn conflict(&self, field: &Field, value: i64) -> bool {
         self.values
             .iter()
             .any(|(f, &v)| v == value && f.equal_axis(field))
     }
-
-    pub fn conflict_asg(&self, other: &Assignment) -> bool {
-        self.values.iter().any(|(f, v)| other.conflict(f, *v))
-    }
 }
 
 impl Display for Assignment {
@@ -43,53 +61,153 @@ impl Display for Assignment {
     }
 }
 
+impl KenKen {
+    /// The possible assignments of `area` under the rules of this puzzle.
+    pub fn possible_assignments(&self, area: &Area) -> Vec<Assignment> {
+        let values = self.values();
+        let asgs = if self.hidden_ops {
+            area.possible_assignments_any_op(&values)
+        } else {
+            area.possible_assignments(&values)
+        };
+        // the enumeration only knows about rows and columns
+        let other_houses = !self.diagonals.is_none() || !self.regions.is_empty();
+        asgs.into_iter()
+            .filter(|asg| !other_houses || !self.conflict(asg, asg))
+            .filter(|asg| {
+                self.givens
+                    .iter()
+                    .all(|(f, v)| asg.get(f).is_none_or(|w| w == *v))
+            })
+            .filter(|asg| !self.killer || distinct_values(asg))
+            .collect()
+    }
+
+    /// Whether two distinct fields of the assignments share a value and a house.
+    pub fn conflict(&self, a: &Assignment, b: &Assignment) -> bool {
+        a.iter().any(|(fa, va)| {
+            b.iter()
+                .any(|(fb, vb)| fa != fb && va == vb && self.same_house(&fa, &fb))
+        })
+    }
+}
+
+fn distinct_values(asg: &Assignment) -> bool {
+    let mut values: Vec<i64> = asg.values.values().cloned().collect();
+    values.sort_unstable();
+    values.windows(2).all(|w| w[0] != w[1])
+}
+
 impl Area {
-    pub fn possible_assignments(&self, size: u16) -> Vec<Assignment> {
+    /// The assignments reaching the target with any operator, for puzzles with
+    /// hidden operators.
+    pub fn possible_assignments_any_op(&self, values: &[i64]) -> Vec<Assignment> {
+        if self.fields.len() == 1 {
+            return Area::new(Type::Single, self.solution, self.fields.clone())
+                .possible_assignments(values);
+        }
+
+        let mut seen = HashSet::new();
+        [Type::Add, Type::Sub, Type::Mul, Type::Div]
+            .into_iter()
+            .flat_map(|ty| {
+                Area::new(ty, self.solution, self.fields.clone()).possible_assignments(values)
+            })
+            .filter(|asg| seen.insert(asg.sorted_values()))
+            .collect()
+    }
+
+    /// The possible assignments with the given sorted value set.
+    pub fn possible_assignments(&self, values: &[i64]) -> Vec<Assignment> {
+        // the bounds used to prune products only hold for positive values
+        let positive = values.iter().all(|&v| v > 0);
         match self.ty {
-            Type::Add => {
-                let mut asgs = Vec::<Assignment>::new();
-                inc_assignments(
-                    &mut asgs,
-                    &IncType::Add,
-                    self.fields.clone(),
-                    None,
-                    0,
-                    self.solution,
-                    size,
-                );
-                asgs
+            Type::Add => inc_assignments(&IncType::Add, &self.fields, self.solution, values),
+            Type::Mul if positive => {
+                inc_assignments(&IncType::Mul, &self.fields, self.solution, values)
             }
-            Type::Mul => {
-                let mut asgs = Vec::<Assignment>::new();
-                inc_assignments(
-                    &mut asgs,
-                    &IncType::Mul,
-                    self.fields.clone(),
-                    None,
-                    1,
-                    self.solution,
-                    size,
-                );
-                asgs
+            Type::Sub => dec_assignments(&DecType::Sub, &self.fields, self.solution, values),
+            Type::Div if positive => {
+                dec_assignments(&DecType::Div, &self.fields, self.solution, values)
             }
-            Type::Sub => dec_assignments(
-                DecType::Sub,
-                self.fields[0],
-                self.fields[1],
-                self.solution,
-                size,
-            ),
-            Type::Div => dec_assignments(
-                DecType::Div,
-                self.fields[0],
-                self.fields[1],
-                self.solution,
-                size,
-            ),
-            Type::Single => vec![Assignment {
-                values: [(self.fields.first().unwrap().clone(), self.solution as u16)].into(),
-            }],
+            Type::Single => [self.solution]
+                .into_iter()
+                .filter(|v| values.contains(v))
+                .map(|v| Assignment {
+                    values: [(*self.fields.first().unwrap(), v)].into(),
+                })
+                .collect(),
+            ty => filtered_assignments(ty, &self.reading_order(), self.solution, values),
+        }
+    }
+}
+
+/// Enumerates areas of the remaining types by a search over the fields in
+/// reading order, pruning values that cannot contribute to the target.
+fn filtered_assignments(
+    ty: Type,
+    fields: &[Field],
+    target: i64,
+    values: &[i64],
+) -> Vec<Assignment> {
+    let mut asgs = Vec::new();
+    filter_values(
+        ty,
+        fields,
+        target,
+        values,
+        &mut vec![],
+        &mut Assignment::empty(),
+        &mut asgs,
+    );
+    asgs
+}
+
+fn admissible(ty: Type, target: i64, values: &[i64]) -> bool {
+    let v = *values.last().unwrap();
+    match ty {
+        Type::Min => v >= target,
+        Type::Max => v <= target,
+        Type::Gcd => target > 0 && v % target == 0,
+        Type::Lcm => v != 0 && target % v == 0,
+        Type::Concat => {
+            let prefix: String = values.iter().map(|v| v.to_string()).collect();
+            target.to_string().starts_with(&prefix)
         }
+        _ => true,
+    }
+}
+
+fn filter_values(
+    ty: Type,
+    fields: &[Field],
+    target: i64,
+    candidates: &[i64],
+    values: &mut Vec<i64>,
+    partial_asg: &mut Assignment,
+    asgs: &mut Vec<Assignment>,
+) {
+    let field = match fields.get(values.len()) {
+        Some(field) => *field,
+        None => {
+            if ty.evaluate(values) == Some(target) {
+                asgs.push(partial_asg.clone());
+            }
+            return;
+        }
+    };
+
+    for &v in candidates {
+        if partial_asg.conflict(&field, v) {
+            continue;
+        }
+        values.push(v);
+        if admissible(ty, target, values) {
+            partial_asg.set(field, v);
+            filter_values(ty, fields, target, candidates, values, partial_asg, asgs);
+            partial_asg.values.remove(&field);
+        }
+        values.pop();
     }
 }
 
@@ -98,49 +216,158 @@ enum IncType {
     Mul,
 }
 
-fn inc_assignments(
-    asgs: &mut Vec<Assignment>,
-    ty: &IncType,
-    rem_fields: Vec<Field>,
-    partial_asg: Option<Assignment>,
-    partial_sol: u64,
-    target_sol: u64,
-    size: u16,
+/// Enumerates the assignments of an `Add` or `Mul` area in two steps: first the
+/// sorted value multisets reaching the target, pruned by the bounds of the
+/// remaining values, then their permutations without row or column conflicts.
+/// `Mul` requires positive values.
+fn inc_assignments(ty: &IncType, fields: &[Field], target: i64, values: &[i64]) -> Vec<Assignment> {
+    let limits = MultisetLimits {
+        ty,
+        target,
+        values,
+        max_repeat: max_repeat(fields),
+    };
+
+    let mut multisets = Vec::new();
+    value_multisets(
+        &limits,
+        fields.len(),
+        0,
+        ty.identity(),
+        &mut vec![],
+        &mut multisets,
+    );
+
+    let mut asgs = Vec::new();
+    for mut values in multisets {
+        permute_values(fields, &mut values, &mut Assignment::empty(), &mut asgs);
+    }
+    asgs
+}
+
+impl IncType {
+    fn identity(&self) -> i64 {
+        match self {
+            IncType::Add => 0,
+            IncType::Mul => 1,
+        }
+    }
+}
+
+/// A value can occur at most once per row and column of the area.
+fn max_repeat(fields: &[Field]) -> usize {
+    let rows = count_distinct(fields.iter().map(|f| f.1));
+    let cols = count_distinct(fields.iter().map(|f| f.0));
+    rows.min(cols)
+}
+
+fn count_distinct<I: Iterator<Item = u16>>(iter: I) -> usize {
+    let mut items: Vec<u16> = iter.collect();
+    items.sort_unstable();
+    items.dedup();
+    items.len()
+}
+
+struct MultisetLimits<'a> {
+    ty: &'a IncType,
+    target: i64,
+    /// The sorted candidate values.
+    values: &'a [i64],
+    max_repeat: usize,
+}
+
+fn value_multisets(
+    limits: &MultisetLimits,
+    remaining: usize,
+    min_idx: usize,
+    partial_sol: i64,
+    partial: &mut Vec<i64>,
+    multisets: &mut Vec<Vec<i64>>,
 ) {
-    if rem_fields.is_empty() {
-        if partial_sol == target_sol {
-            asgs.push(partial_asg.unwrap())
-        }
-    } else {
-        //for field in &rem_fields {
-        let field = rem_fields.first().unwrap();
-        for v in 1..=size {
-            let mut asg = if let Some(asg) = &partial_asg {
-                asg.clone()
-            } else {
-                Assignment::empty()
-            };
-
-            if !asg.conflict(field, v) {
-                asg.set(*field, v);
-
-                let new_partial_sol = match ty {
-                    IncType::Add => partial_sol + v as u64,
-                    IncType::Mul => partial_sol * v as u64,
-                };
+    if remaining == 0 {
+        if partial_sol == limits.target {
+            multisets.push(partial.clone());
+        }
+        return;
+    }
 
-                inc_assignments(
-                    asgs,
-                    ty,
-                    rem_fields.iter().filter(|&f| f != field).cloned().collect(),
-                    Some(asg),
-                    new_partial_sol,
-                    target_sol,
-                    size,
-                );
+    let max = match limits.values.last() {
+        Some(&max) => max,
+        None => return,
+    };
+    for (idx, &v) in limits.values.iter().enumerate().skip(min_idx) {
+        let repeats = partial.iter().rev().take_while(|&&p| p == v).count();
+        if repeats >= limits.max_repeat {
+            continue;
+        }
+
+        let rest = remaining as u32 - 1;
+        let new_partial_sol = match limits.ty {
+            IncType::Add => {
+                let sum = partial_sol + v;
+                // all following values are at least v and at most max
+                if sum + rest as i64 * v > limits.target {
+                    break;
+                }
+                if sum + rest as i64 * max < limits.target {
+                    continue;
+                }
+                sum
             }
+            IncType::Mul => {
+                let product = partial_sol * v;
+                if limits.target % product != 0 {
+                    continue;
+                }
+                let quotient = limits.target / product;
+                if v.checked_pow(rest).is_none_or(|min| min > quotient) {
+                    break;
+                }
+                if max.checked_pow(rest).is_some_and(|max| max < quotient) {
+                    continue;
+                }
+                product
+            }
+        };
+
+        partial.push(v);
+        value_multisets(
+            limits,
+            remaining - 1,
+            idx,
+            new_partial_sol,
+            partial,
+            multisets,
+        );
+        partial.pop();
+    }
+}
+
+/// Distributes the sorted `values` over `fields` in every conflict-free way.
+fn permute_values(
+    fields: &[Field],
+    values: &mut Vec<i64>,
+    partial_asg: &mut Assignment,
+    asgs: &mut Vec<Assignment>,
+) {
+    let field = match fields.first() {
+        Some(field) => field,
+        None => {
+            asgs.push(partial_asg.clone());
+            return;
+        }
+    };
+
+    for i in 0..values.len() {
+        let v = values[i];
+        if (i > 0 && values[i - 1] == v) || partial_asg.conflict(field, v) {
+            continue;
         }
-        //}
+        values.remove(i);
+        partial_asg.set(*field, v);
+        permute_values(&fields[1..], values, partial_asg, asgs);
+        partial_asg.values.remove(field);
+        values.insert(i, v);
     }
 }
 
@@ -149,39 +376,41 @@ enum DecType {
     Div,
 }
 
-fn dec_assignments(
-    ty: DecType,
-    field1: Field,
-    field2: Field,
-    solution: u64,
-    size: u16,
-) -> Vec<Assignment> {
-    let mut asgs = Vec::<Assignment>::new();
-    for i in 1..=size {
-        for j in 1..=size {
-            if i < j {
-                let sol = match ty {
-                    DecType::Div => {
-                        let tmp = j / i;
-                        if tmp * i == j {
-                            tmp
-                        } else {
-                            0
-                        }
-                    }
-                    DecType::Sub => j - i,
-                };
-                if sol as u64 == solution {
-                    let mut asg1 = Assignment::empty();
-                    asg1.set(field1, i);
-                    asg1.set(field2, j);
-                    let mut asg2 = Assignment::empty();
-                    asg2.set(field2, i);
-                    asg2.set(field1, j);
-                    asgs.push(asg1);
-                    asgs.push(asg2);
-                }
-            }
+/// Enumerates `Sub` and `Div` areas of any size, where the largest value minus
+/// (divided by) all other values equals the target. For each candidate largest
+/// value, the others are the `Add` (`Mul`) multisets of the remainder. `Div`
+/// requires positive values.
+fn dec_assignments(ty: &DecType, fields: &[Field], target: i64, values: &[i64]) -> Vec<Assignment> {
+    let mut asgs = Vec::new();
+    if fields.len() < 2 {
+        return asgs;
+    }
+
+    for (idx, &largest) in values.iter().enumerate() {
+        let (rest_ty, rest_target) = match ty {
+            DecType::Sub => (IncType::Add, largest - target),
+            DecType::Div if target > 0 && largest % target == 0 => (IncType::Mul, largest / target),
+            _ => continue,
+        };
+        let limits = MultisetLimits {
+            ty: &rest_ty,
+            target: rest_target,
+            values: &values[..=idx],
+            max_repeat: max_repeat(fields),
+        };
+
+        let mut multisets = Vec::new();
+        value_multisets(
+            &limits,
+            fields.len() - 1,
+            0,
+            rest_ty.identity(),
+            &mut vec![],
+            &mut multisets,
+        );
+        for mut values in multisets {
+            values.push(largest);
+            permute_values(fields, &mut values, &mut Assignment::empty(), &mut asgs);
         }
     }
     asgs
@@ -190,28 +419,40 @@ fn dec_assignments(
 #[cfg(test)]
 mod test_asg_gen {
     use super::*;
+    use crate::kenken::default_values;
 
     #[test]
     fn test_add_two_fields() {
         let area = Area::new(Type::Add, 10, vec![Field(0, 0), Field(1, 0)]);
-        let asgs = area.possible_assignments(9);
+        let asgs = area.possible_assignments(&default_values(9));
         assert_eq!(asgs.len(), 8)
     }
 
     #[test]
     fn test_add_three_straight_fields() {
         let area = Area::new(Type::Add, 10, vec![Field(0, 0), Field(1, 0), Field(2, 0)]);
-        let asgs = area.possible_assignments(9);
+        let asgs = area.possible_assignments(&default_values(9));
         assert_eq!(asgs.len(), 24)
     }
 
     #[test]
     fn test_add_three_fields_corner() {
         let area = Area::new(Type::Add, 10, vec![Field(0, 0), Field(1, 0), Field(0, 1)]);
-        let asgs = area.possible_assignments(9);
+        let asgs = area.possible_assignments(&default_values(9));
         assert_eq!(asgs.len(), 28)
     }
 
+    #[test]
+    fn test_killer() {
+        // the corner may no longer repeat 1, 2, 3 or 4 around 8, 6, 4 or 2
+        let area = Area::new(Type::Add, 10, vec![Field(0, 0), Field(1, 0), Field(0, 1)]);
+        let kenken = KenKen {
+            killer: true,
+            ..KenKen::new(0, 9, vec![area])
+        };
+        assert_eq!(kenken.possible_assignments(&kenken.areas[0]).len(), 24)
+    }
+
     #[test]
     fn test_add_four_fields_rect() {
         let area = Area::new(
@@ -219,7 +460,7 @@ mod test_asg_gen {
             8,
             vec![Field(0, 0), Field(1, 0), Field(0, 1), Field(1, 1)],
         );
-        let asgs = area.possible_assignments(9);
+        let asgs = area.possible_assignments(&default_values(9));
         println!("{:?}", asgs);
         assert_eq!(asgs.len(), 10)
     }
@@ -227,7 +468,7 @@ mod test_asg_gen {
     #[test]
     fn test_sub() {
         let area = Area::new(Type::Sub, 4, vec![Field(0, 0), Field(1, 0)]);
-        let asgs = area.possible_assignments(9);
+        let asgs = area.possible_assignments(&default_values(9));
         println!("{:?}", asgs);
         assert_eq!(asgs.len(), 10)
     }
@@ -235,16 +476,266 @@ mod test_asg_gen {
     #[test]
     fn test_div() {
         let area = Area::new(Type::Div, 2, vec![Field(0, 0), Field(1, 0)]);
-        let asgs = area.possible_assignments(9);
+        let asgs = area.possible_assignments(&default_values(9));
         println!("{:?}", asgs);
         assert_eq!(asgs.len(), 8)
     }
 
+    #[test]
+    fn test_sub_three_fields() {
+        // 8 - 2 - 1 and 9 - 3 - 1 in any order, 7 - 1 - 1 and 9 - 2 - 2 with the
+        // repeated value on the diagonal
+        let area = Area::new(Type::Sub, 5, vec![Field(0, 0), Field(1, 0), Field(1, 1)]);
+        let asgs = area.possible_assignments(&default_values(9));
+        assert_eq!(asgs.len(), 2 * 6 + 2)
+    }
+
+    #[test]
+    fn test_div_three_fields() {
+        // 4 / 2 / 1, 6 / 3 / 1 and 8 / 4 / 1 in any order, 2 / 1 / 1 and 8 / 2 / 2
+        // with the repeated value on the diagonal
+        let area = Area::new(Type::Div, 2, vec![Field(0, 0), Field(1, 0), Field(1, 1)]);
+        let asgs = area.possible_assignments(&default_values(9));
+        assert_eq!(asgs.len(), 3 * 6 + 2)
+    }
+
+    #[test]
+    fn test_any_op() {
+        // 1 + 5, 2 + 4, 3 + 3; 1 * 6, 2 * 3; 7 - 1, 8 - 2, 9 - 3; 6 / 1 is 1 * 6
+        let area = Area::new(Type::Single, 6, vec![Field(0, 0), Field(1, 1)]);
+        let asgs = area.possible_assignments_any_op(&default_values(9));
+        assert_eq!(asgs.len(), 5 + 4 + 6)
+    }
+
+    #[test]
+    fn test_mod() {
+        // 3 % 2, 5 % 2, 7 % 2, 9 % 2, 4 % 3, 7 % 3, 5 % 4, 9 % 4, 6 % 5, 7 % 6, 8 % 7, 9 % 8
+        let area = Area::new(Type::Mod, 1, vec![Field(0, 0), Field(1, 0)]);
+        let asgs = area.possible_assignments(&default_values(9));
+        assert_eq!(asgs.len(), 2 * 12)
+    }
+
+    #[test]
+    fn test_min_max() {
+        // min 7: {7, 8}, {7, 9} in both orders; max 2: {1, 2} in both orders
+        let area = Area::new(Type::Min, 7, vec![Field(0, 0), Field(1, 0)]);
+        assert_eq!(area.possible_assignments(&default_values(9)).len(), 4);
+        let area = Area::new(Type::Max, 2, vec![Field(0, 0), Field(1, 0)]);
+        assert_eq!(area.possible_assignments(&default_values(9)).len(), 2)
+    }
+
+    #[test]
+    fn test_gcd_lcm() {
+        // gcd 3: {3, 6}, {3, 9}, {6, 9}; lcm 6: {1, 6}, {2, 3}, {2, 6}, {3, 6}
+        let area = Area::new(Type::Gcd, 3, vec![Field(0, 0), Field(1, 0)]);
+        assert_eq!(area.possible_assignments(&default_values(9)).len(), 2 * 3);
+        let area = Area::new(Type::Lcm, 6, vec![Field(0, 0), Field(1, 0)]);
+        assert_eq!(area.possible_assignments(&default_values(9)).len(), 2 * 4)
+    }
+
+    #[test]
+    fn test_concat() {
+        // the upper field comes first in reading order
+        let area = Area::new(
+            Type::Concat,
+            312,
+            vec![Field(1, 1), Field(0, 1), Field(1, 0)],
+        );
+        let asgs = area.possible_assignments(&default_values(9));
+        assert_eq!(asgs.len(), 1);
+        assert_eq!(asgs[0].get(&Field(1, 0)), Some(3));
+        assert_eq!(asgs[0].get(&Field(0, 1)), Some(1));
+        assert_eq!(asgs[0].get(&Field(1, 1)), Some(2))
+    }
+
+    #[test]
+    fn test_concat_zero() {
+        let values = [0, 1, 2, 3];
+        let area = Area::new(Type::Concat, 20, vec![Field(0, 0), Field(1, 0)]);
+        let asgs = area.possible_assignments(&values);
+        assert_eq!(asgs.len(), 1);
+        assert_eq!(asgs[0].get(&Field(1, 0)), Some(0));
+        // a leading zero would read as the single digit
+        assert_eq!(Type::Concat.evaluate(&[0, 2]), None);
+        assert_eq!(Type::Concat.evaluate(&[-1, 2]), None);
+        // every target found by `evaluate` is reached by its values
+        for a in values {
+            for b in values.iter().filter(|&&b| b != a) {
+                if let Some(target) = Type::Concat.evaluate(&[a, *b]) {
+                    let area = Area::new(Type::Concat, target, vec![Field(0, 0), Field(1, 0)]);
+                    assert!(area
+                        .possible_assignments(&values)
+                        .iter()
+                        .any(|asg| asg.get(&Field(0, 0)) == Some(a)));
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_single() {
         let area = Area::new(Type::Single, 5, vec![Field(0, 0)]);
-        let asgs = area.possible_assignments(9);
+        let asgs = area.possible_assignments(&default_values(9));
         println!("{:?}", asgs);
         assert_eq!(asgs.len(), 1)
     }
+
+    /// The exhaustive enumeration used before multisets were pruned, as reference.
+    fn naive_inc_assignments(
+        asgs: &mut Vec<Assignment>,
+        ty: &IncType,
+        rem_fields: Vec<Field>,
+        partial_asg: Option<Assignment>,
+        partial_sol: i64,
+        target_sol: i64,
+        values: &[i64],
+    ) {
+        if rem_fields.is_empty() {
+            if partial_sol == target_sol {
+                asgs.push(partial_asg.unwrap())
+            }
+        } else {
+            //for field in &rem_fields {
+            let field = rem_fields.first().unwrap();
+            for &v in values {
+                let mut asg = if let Some(asg) = &partial_asg {
+                    asg.clone()
+                } else {
+                    Assignment::empty()
+                };
+
+                if !asg.conflict(field, v) {
+                    asg.set(*field, v);
+
+                    let new_partial_sol = match ty {
+                        IncType::Add => partial_sol + v,
+                        IncType::Mul => partial_sol * v,
+                    };
+
+                    naive_inc_assignments(
+                        asgs,
+                        ty,
+                        rem_fields.iter().filter(|&f| f != field).cloned().collect(),
+                        Some(asg),
+                        new_partial_sol,
+                        target_sol,
+                        values,
+                    );
+                }
+            }
+            //}
+        }
+    }
+
+    fn naive(area: &Area, values: &[i64]) -> Vec<Assignment> {
+        let ty = match area.ty {
+            Type::Add => IncType::Add,
+            _ => IncType::Mul,
+        };
+        let start = if area.ty == Type::Add { 0 } else { 1 };
+        let mut asgs = vec![];
+        naive_inc_assignments(
+            &mut asgs,
+            &ty,
+            area.fields.clone(),
+            None,
+            start,
+            area.solution,
+            values,
+        );
+        asgs
+    }
+
+    fn sorted(asgs: Vec<Assignment>) -> Vec<Vec<(Field, i64)>> {
+        let mut asgs: Vec<Vec<(Field, i64)>> = asgs
+            .into_iter()
+            .map(|asg| {
+                let mut values: Vec<_> = asg.iter().collect();
+                values.sort();
+                values
+            })
+            .collect();
+        asgs.sort();
+        asgs
+    }
+
+    /// The puzzles of the fixture directories, which the library never writes to.
+    fn puzzles() -> Vec<(String, crate::kenken::KenKen)> {
+        crate::canon::read_puzzles(&["testdata".into(), "old".into()])
+            .unwrap()
+            .into_iter()
+            .map(|(path, kenken)| (path.display().to_string(), kenken))
+            .collect()
+    }
+
+    #[test]
+    fn test_pruned_matches_naive() {
+        for (_, kenken) in puzzles() {
+            for area in kenken
+                .areas
+                .iter()
+                .filter(|a| matches!(a.ty, Type::Add | Type::Mul))
+            {
+                assert_eq!(
+                    sorted(area.possible_assignments(&kenken.values())),
+                    sorted(naive(area, &kenken.values())),
+                    "{:?}",
+                    area
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_value_sets() {
+        let fields = vec![Field(0, 0), Field(1, 0), Field(1, 1)];
+        for values in [(0..=5).collect::<Vec<i64>>(), vec![-3, -2, -1, 1, 2, 3]] {
+            for target in -6..=6 {
+                for ty in [Type::Add, Type::Mul] {
+                    let area = Area::new(ty, target, fields.clone());
+                    assert_eq!(
+                        sorted(area.possible_assignments(&values)),
+                        sorted(naive(&area, &values)),
+                        "{:?} {:?}",
+                        values,
+                        area
+                    );
+                }
+            }
+        }
+
+        // -1 - -3, 0 - -2, 1 - -1, 2 - 0 and 3 - 1 in either order
+        let area = Area::new(Type::Sub, 2, vec![Field(0, 0), Field(1, 0)]);
+        assert_eq!(
+            area.possible_assignments(&[-3, -2, -1, 0, 1, 2, 3]).len(),
+            2 * 5
+        );
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture bench_`.
+    #[test]
+    #[ignore]
+    fn bench_inc_assignments() {
+        for (name, kenken) in puzzles() {
+            let areas: Vec<&Area> = kenken
+                .areas
+                .iter()
+                .filter(|a| matches!(a.ty, Type::Add | Type::Mul))
+                .collect();
+            let start = std::time::Instant::now();
+            let pruned: usize = areas
+                .iter()
+                .map(|a| a.possible_assignments(&kenken.values()).len())
+                .sum();
+            let pruned_time = start.elapsed();
+            let start = std::time::Instant::now();
+            let naive: usize = areas.iter().map(|a| naive(a, &kenken.values()).len()).sum();
+            let naive_time = start.elapsed();
+            assert_eq!(pruned, naive);
+            println!(
+                "{}: {} assignments, pruned {:.2?}, naive {:.2?}",
+                name, pruned, pruned_time, naive_time
+            );
+        }
+    }
 }
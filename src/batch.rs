@@ -0,0 +1,319 @@
+use std::{fs::File, io::BufWriter, path::Path, time::Duration};
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    gen::{self, DifficultyConfig},
+    kenken::{Diagonals, KenKen, Type},
+    library::{Entry, Library},
+    solve::{Backend, Outcome},
+};
+
+/// A sweep over generator parameters: every combination of the values is
+/// generated `repeat` times.
+pub struct BatchConfig {
+    pub sizes: Vec<u16>,
+    pub size_factors: Vec<f32>,
+    /// Relative weights of addition, subtraction, multiplication and division,
+    /// normalized for each combination.
+    pub add: Vec<f32>,
+    pub sub: Vec<f32>,
+    pub mul: Vec<f32>,
+    pub div: Vec<f32>,
+    pub repeat: usize,
+    pub unique: bool,
+    /// The solver whose time is recorded.
+    pub solver: Backend,
+    pub timeout: Option<Duration>,
+}
+
+/// The parameters of one generated puzzle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Params {
+    pub size: u16,
+    pub size_factor: f32,
+    /// Probabilities of addition, subtraction, multiplication and division.
+    pub ops: [f32; 4],
+}
+
+impl BatchConfig {
+    /// The parameters of every puzzle, skipping operator weights which are all zero.
+    pub fn jobs(&self) -> Vec<Params> {
+        let mut jobs = vec![];
+        for &size in &self.sizes {
+            for &size_factor in &self.size_factors {
+                for &add in &self.add {
+                    for &sub in &self.sub {
+                        for &mul in &self.mul {
+                            for &div in &self.div {
+                                let total = add + sub + mul + div;
+                                if total <= 0.0 {
+                                    continue;
+                                }
+                                let ops = [add / total, sub / total, mul / total, div / total];
+                                jobs.extend((0..self.repeat).map(|_| Params {
+                                    size,
+                                    size_factor,
+                                    ops,
+                                }));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        jobs
+    }
+}
+
+impl Params {
+    fn difficulty(&self, unique: bool, seed: u64) -> DifficultyConfig {
+        DifficultyConfig {
+            p_add: self.ops[0],
+            p_sub: self.ops[1],
+            p_mul: self.ops[2],
+            p_div: self.ops[3],
+            p_mod: 0.0,
+            p_min: 0.0,
+            p_max: 0.0,
+            p_gcd: 0.0,
+            p_lcm: 0.0,
+            p_concat: 0.0,
+            size_factor: self.size_factor,
+            shapes: None,
+            hidden_ops: false,
+            values: None,
+            diagonals: Diagonals::None,
+            jigsaw: false,
+            killer: false,
+            symmetry: Default::default(),
+            unique,
+            givens: false,
+            seed,
+        }
+    }
+}
+
+/// The measurements of one generated puzzle, one CSV row each.
+#[derive(Debug, Serialize)]
+pub struct Metrics {
+    pub id: u64,
+    pub seed: u64,
+    pub size: u16,
+    pub size_factor: f32,
+    pub p_add: f32,
+    pub p_sub: f32,
+    pub p_mul: f32,
+    pub p_div: f32,
+    /// Total number of possible assignments of all areas.
+    pub assignments: usize,
+    /// `solved`, `infeasible` or `timeout`.
+    pub outcome: &'static str,
+    /// Seconds the solver took to find a solution.
+    pub solve_time: f64,
+    pub nodes: u64,
+    pub areas: usize,
+    /// Number of areas of each size as `size:count` pairs, e.g. `1:3 2:10 3:4`.
+    pub area_sizes: String,
+    pub add: usize,
+    pub sub: usize,
+    pub mul: usize,
+    pub div: usize,
+    pub single: usize,
+}
+
+impl Metrics {
+    fn measure(kenken: &KenKen, seed: u64, params: &Params, config: &BatchConfig) -> Result<Self> {
+        let solver = config.solver.solver(config.timeout);
+        let (outcome, stats) = solver.solve(kenken)?;
+
+        let mut histogram = vec![0; kenken.size as usize * kenken.size as usize + 1];
+        for area in &kenken.areas {
+            histogram[area.fields.len()] += 1;
+        }
+        let area_sizes = histogram
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(size, count)| format!("{}:{}", size, count))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let count = |typ: Type| kenken.areas.iter().filter(|area| area.ty == typ).count();
+
+        Ok(Metrics {
+            id: kenken.id,
+            seed,
+            size: params.size,
+            size_factor: params.size_factor,
+            p_add: params.ops[0],
+            p_sub: params.ops[1],
+            p_mul: params.ops[2],
+            p_div: params.ops[3],
+            assignments: kenken.total_number_of_assignments(),
+            outcome: match outcome {
+                Outcome::Solved(_) => "solved",
+                Outcome::Infeasible => "infeasible",
+                Outcome::TimedOut => "timeout",
+            },
+            solve_time: stats.elapsed.as_secs_f64(),
+            nodes: stats.nodes,
+            areas: kenken.areas.len(),
+            area_sizes,
+            add: count(Type::Add),
+            sub: count(Type::Sub),
+            mul: count(Type::Mul),
+            div: count(Type::Div),
+            single: count(Type::Single),
+        })
+    }
+}
+
+/// Generates the puzzles of `config` in parallel, adds them to `library`
+/// and returns the metrics of each job in the order of `BatchConfig::jobs`,
+/// or why it failed. The id of a failed job is released again.
+pub fn run(config: &BatchConfig, library: &Library) -> Vec<Result<Metrics>> {
+    config
+        .jobs()
+        .par_iter()
+        .map(|params| {
+            let id = library.reserve()?;
+            let seed = rand::random();
+            let job = || -> Result<Metrics> {
+                let kenken =
+                    gen::generate(id, params.size, &params.difficulty(config.unique, seed))?;
+                let metrics = Metrics::measure(&kenken, seed, params, config)?;
+                library.add(&kenken, Entry::new(&kenken, Some(seed), "batch-generate"))?;
+                Ok(metrics)
+            };
+            job()
+                .or_else(|e| {
+                    library.release(id)?;
+                    Err(e)
+                })
+                .with_context(|| {
+                    format!(
+                        "size {}, size factor {}, operators {:?}, seed {}",
+                        params.size, params.size_factor, params.ops, seed
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Writes the metrics as JSON if the path ends in `.json`, as CSV otherwise.
+pub fn write_metrics(metrics: &[Metrics], path: &Path) -> Result<()> {
+    if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), metrics)?;
+    } else {
+        let mut wtr = csv::Writer::from_path(path)?;
+        for row in metrics {
+            wtr.serialize(row)?;
+        }
+        wtr.flush()?;
+    }
+    Ok(())
+}
+
+/// Parses numbers given as values and stepped inclusive ranges, e.g.
+/// `0.3..=0.8:0.1` or `0,0.5,1`. The step defaults to 1.
+pub fn parse_range(s: &str) -> Result<Vec<f32>, String> {
+    let mut values = vec![];
+    for part in s.split(',').map(str::trim) {
+        let parse = |v: &str| {
+            v.trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .ok_or_else(|| format!("invalid number '{}'", v))
+        };
+        match part.split_once("..=") {
+            Some((from, rest)) => {
+                let (to, step) = match rest.split_once(':') {
+                    Some((to, step)) => (parse(to)?, parse(step)?),
+                    None => (parse(rest)?, 1.0),
+                };
+                let from = parse(from)?;
+                if step <= 0.0 {
+                    return Err(format!("step of '{}' must be positive", part));
+                }
+                // rounded, so that 0.3 + 0.1 * 2 is 0.5 rather than 0.50000006
+                let steps = ((to - from) / step + 1e-4).floor();
+                values.extend(
+                    (0..=steps as i64).map(|i| ((from + i as f32 * step) * 1e4).round() / 1e4),
+                );
+            }
+            None => values.push(parse(part)?),
+        }
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod test_batch {
+    use super::*;
+
+    #[test]
+    fn test_jobs() {
+        assert_eq!(
+            parse_range("0.3..=0.8:0.1"),
+            Ok(vec![0.3, 0.4, 0.5, 0.6, 0.7, 0.8])
+        );
+        assert_eq!(
+            parse_range("0, 2..=4, 0.5"),
+            Ok(vec![0.0, 2.0, 3.0, 4.0, 0.5])
+        );
+        assert!(parse_range("1..=2:0").is_err());
+
+        let config = BatchConfig {
+            sizes: vec![4, 5],
+            size_factors: vec![0.4, 0.6],
+            add: vec![0.0, 1.0],
+            sub: vec![0.0],
+            mul: vec![0.0, 3.0],
+            div: vec![0.0],
+            repeat: 3,
+            unique: false,
+            solver: Backend::Search,
+            timeout: None,
+        };
+        // the all-zero weights are skipped
+        let jobs = config.jobs();
+        assert_eq!(jobs.len(), 2 * 2 * 3 * 3);
+        assert!(jobs.iter().any(|job| job.ops == [0.25, 0.0, 0.75, 0.0]));
+        assert!(jobs
+            .iter()
+            .all(|job| (job.ops.iter().sum::<f32>() - 1.0).abs() < 1e-4));
+    }
+
+    #[test]
+    fn test_failed_jobs() {
+        let dir = std::env::temp_dir().join(format!("kenken-batch-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let library = Library::new(&dir, &dir);
+        // only subtraction, which fails for areas with repeated values
+        let config = BatchConfig {
+            sizes: vec![4],
+            size_factors: vec![0.8],
+            add: vec![0.0],
+            sub: vec![1.0],
+            mul: vec![0.0],
+            div: vec![0.0],
+            repeat: 8,
+            unique: false,
+            solver: Backend::Search,
+            timeout: None,
+        };
+        let results = run(&config, &library);
+        assert_eq!(results.len(), 8);
+        // no reserved ids are left behind
+        let ok = results.iter().filter(|r| r.is_ok()).count();
+        assert_eq!(library.entries().unwrap().len(), ok);
+        assert_eq!(
+            std::fs::read_dir(&dir).map_or(0, |d| d.count()),
+            if ok > 0 { ok + 1 } else { 0 }
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,150 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use anyhow::Result;
+
+use clap::ArgEnum;
+
+use crate::{
+    kenken::{Field, KenKen},
+    transform::Transform,
+};
+
+/// Sorts the fields of every area, the areas, the givens and the regions in
+/// reading order, which does not change the puzzle.
+pub fn normalize(kenken: &mut KenKen) {
+    let key = |f: &Field| (f.1, f.0);
+    for area in &mut kenken.areas {
+        area.fields.sort_by_key(key);
+    }
+    kenken.areas.sort_by_key(|area| key(&area.fields[0]));
+    kenken.givens.sort_by_key(|(f, _)| key(f));
+    for region in &mut kenken.regions {
+        region.sort_by_key(key);
+    }
+    kenken.regions.sort_by_key(|region| key(&region[0]));
+}
+
+/// The canonical form of a puzzle: of its normalized images under the
+/// symmetries of the grid, the least as RON, with id 0 and without the
+/// symmetry of the layout. Two puzzles are the same up to rotation,
+/// reflection and transposition exactly if their canonical forms are equal.
+/// Symmetries which change the digits of a `Concat` area are left out.
+pub fn canonical(kenken: &KenKen) -> KenKen {
+    let images = Transform::value_variants()
+        .iter()
+        .filter_map(|t| kenken.transformed(*t).ok());
+    std::iter::once(kenken.clone())
+        .chain(images)
+        .map(|mut image| {
+            image.id = 0;
+            image.symmetry = Default::default();
+            normalize(&mut image);
+            let key = ron::to_string(&image).unwrap();
+            (key, image)
+        })
+        .min_by(|(a, _), (b, _)| a.cmp(b))
+        .unwrap()
+        .1
+}
+
+/// The puzzles of the `.ron` files in `dirs`, skipping files which are not
+/// puzzles (with a warning) and ids reserved by a running generator.
+pub fn read_puzzles(dirs: &[PathBuf]) -> Result<Vec<(PathBuf, KenKen)>> {
+    let mut puzzles = vec![];
+    for dir in dirs {
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?;
+        paths.sort();
+        for path in paths {
+            if path.extension().is_none_or(|ext| ext != "ron") || path.ends_with("index.ron") {
+                continue;
+            }
+            let input = fs::read_to_string(&path)?;
+            if input.is_empty() {
+                continue;
+            }
+            match ron::from_str::<KenKen>(&input) {
+                Ok(kenken) => puzzles.push((path, kenken)),
+                Err(e) => eprintln!("Skipping {}: {}", path.display(), e),
+            }
+        }
+    }
+    Ok(puzzles)
+}
+
+/// The groups of at least two puzzles with the same canonical form, as
+/// indices into `puzzles`, in the order of their first puzzle.
+pub fn duplicates(puzzles: &[KenKen]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = vec![];
+    let mut by_form: HashMap<String, usize> = HashMap::new();
+    for (idx, kenken) in puzzles.iter().enumerate() {
+        let form = ron::to_string(&canonical(kenken)).unwrap();
+        match by_form.get(&form) {
+            Some(&group) => groups[group].push(idx),
+            None => {
+                by_form.insert(form, groups.len());
+                groups.push(vec![idx]);
+            }
+        }
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+#[cfg(test)]
+mod test_canon {
+    use super::*;
+    use crate::kenken::{Area, Diagonals, Type};
+
+    fn puzzle(areas: Vec<Area>) -> KenKen {
+        KenKen {
+            diagonals: Diagonals::Main,
+            givens: vec![(Field(2, 0), 3)],
+            ..KenKen::new(1, 3, areas)
+        }
+    }
+
+    #[test]
+    fn test_canonical() {
+        let kenken = puzzle(vec![
+            Area::new(Type::Add, 6, vec![Field(0, 0), Field(1, 0), Field(0, 1)]),
+            Area::new(Type::Single, 3, vec![Field(2, 0)]),
+            Area::new(Type::Mul, 2, vec![Field(2, 1), Field(1, 1)]),
+            Area::new(Type::Sub, 2, vec![Field(0, 2), Field(1, 2)]),
+            Area::new(Type::Single, 2, vec![Field(2, 2)]),
+        ]);
+        let form = ron::to_string(&canonical(&kenken)).unwrap();
+        for transform in Transform::value_variants() {
+            let image = kenken.transformed(*transform).unwrap();
+            assert_eq!(
+                ron::to_string(&canonical(&image)).unwrap(),
+                form,
+                "{:?}",
+                transform
+            );
+        }
+
+        let mut other = kenken.transformed(Transform::Rot270).unwrap();
+        other.areas[0].solution = 7;
+        let rotated = kenken.transformed(Transform::Rot270).unwrap();
+        assert_eq!(
+            duplicates(&[kenken, other, puzzle(vec![]), rotated]),
+            vec![vec![0, 3]]
+        );
+    }
+
+    #[test]
+    fn test_concat() {
+        // 12 read from the left is 21 in the mirror image, but not upside down
+        let kenken = puzzle(vec![Area::new(
+            Type::Concat,
+            12,
+            vec![Field(0, 0), Field(1, 0)],
+        )]);
+        assert!(kenken.transformed(Transform::Mirror).is_err());
+        assert!(kenken.transformed(Transform::Flip).is_ok());
+        let flipped = kenken.transformed(Transform::Flip).unwrap();
+        assert_eq!(duplicates(&[kenken, flipped]), vec![vec![0, 1]]);
+    }
+}
@@ -14,6 +14,10 @@ use rusttype::{Font, Scale};
 
 const BLACK: Luma<u8> = Luma([0]);
 const WHITE: Luma<u8> = Luma([255]);
+const DIAGONAL: Luma<u8> = Luma([220]);
+const REGION: Luma<u8> = Luma([140]);
+/// The shade of the fields with the most candidates in a heatmap.
+const HOTTEST: u8 = 90;
 
 pub struct DrawConfig {
     field_size: u16,
@@ -22,6 +26,10 @@ pub struct DrawConfig {
     offset: u16,
     target_x: u16,
     target_y: u16,
+    target_font: f32,
+    /// Candidate counts of the fields in reading order, drawn as shades from
+    /// white for forced fields to dark gray, with the count in the corner.
+    pub heatmap: Option<Vec<usize>>,
 }
 
 pub const DEFAULT_CONFIG: DrawConfig = DrawConfig {
@@ -31,8 +39,35 @@ pub const DEFAULT_CONFIG: DrawConfig = DrawConfig {
     offset: 50,
     target_x: 20,
     target_y: 20,
+    target_font: 50.0,
+    heatmap: None,
 };
 
+impl DrawConfig {
+    /// The default layout, with smaller fields beyond 9x9 and the target font
+    /// shrunk until the longest label fits its field.
+    pub fn scaled(kenken: &KenKen) -> DrawConfig {
+        let field_size = if kenken.size <= 9 {
+            DEFAULT_CONFIG.field_size
+        } else {
+            (DEFAULT_CONFIG.field_size * 9 / kenken.size).max(100)
+        };
+        let longest = kenken
+            .areas
+            .iter()
+            .map(|area| kenken.label(area, glyph).chars().count())
+            .max()
+            .unwrap_or(1);
+        // glyphs are about 0.6 em wide
+        let fitting = (field_size - DEFAULT_CONFIG.target_x) as f32 / (0.6 * longest as f32);
+        DrawConfig {
+            field_size,
+            target_font: DEFAULT_CONFIG.target_font.min(fitting),
+            ..DEFAULT_CONFIG
+        }
+    }
+}
+
 pub fn draw(
     kenken: &KenKen,
     file: &PathBuf,
@@ -52,6 +87,13 @@ pub fn draw(
         y: font_size,
     };
 
+    print_diagonals(&mut img, kenken, config);
+    print_heatmap(&mut img, kenken, config, &font);
+    if kenken.killer {
+        print_cages(&mut img, kenken, config);
+    } else {
+        print_regions(&mut img, kenken, config);
+    }
     print_horizontal_separators(&mut img, kenken, 0, config);
     print_vertical_separators(&mut img, kenken, 0, config);
 
@@ -60,9 +102,24 @@ pub fn draw(
         print_horizontal_separators(&mut img, kenken, i + 1, config);
     }
 
-    print_targets(&mut img, kenken, config, scale, &font);
+    let target_scale = Scale {
+        x: config.target_font,
+        y: config.target_font,
+    };
+    print_targets(&mut img, kenken, config, target_scale, &font);
+    print_givens(&mut img, kenken, config, &font);
 
-    if let Some(gen_config) = gen_config {
+    if let Some(gen_config) = gen_config.filter(|_| kenken.killer) {
+        draw_text_mut(
+            &mut img,
+            BLACK,
+            config.offset as i32,
+            10,
+            scale,
+            &font,
+            &format!("Killer {} (s = {})", kenken.id, gen_config.size_factor),
+        );
+    } else if let Some(gen_config) = gen_config {
         draw_text_mut(
             &mut img,
             BLACK,
@@ -96,6 +153,186 @@ pub fn draw(
     Ok(())
 }
 
+/// The symbols of drawn puzzles, which differ from the printed ones where
+/// the font has a better glyph.
+fn glyph(ty: Type) -> &'static str {
+    match ty {
+        Type::Div => "÷",
+        Type::Mod => "mod",
+        ty => ty.symbol(),
+    }
+}
+
+/// Shades the fields of the constrained diagonals.
+fn print_diagonals(c: &mut GrayImage, kenken: &KenKen, config: &DrawConfig) {
+    for i in 0..kenken.size {
+        for j in 0..kenken.size {
+            let field = Field(i, j);
+            if kenken.on_main_diagonal(&field) || kenken.on_anti_diagonal(&field) {
+                draw_filled_rect_mut(
+                    c,
+                    Rect::at(
+                        (i * config.field_size + config.offset) as i32,
+                        (j * config.field_size + config.offset + 100) as i32,
+                    )
+                    .of_size(config.field_size as u32, config.field_size as u32),
+                    DIAGONAL,
+                );
+            }
+        }
+    }
+}
+
+/// Shades every field by its number of candidates, relative to the size.
+fn print_heatmap<'a>(c: &mut GrayImage, kenken: &KenKen, config: &DrawConfig, font: &'a Font<'a>) {
+    let counts = match &config.heatmap {
+        Some(counts) => counts,
+        None => return,
+    };
+    let size = kenken.size as usize;
+    let font_size = config.field_size as f32 / 5.0;
+    let scale = Scale {
+        x: font_size,
+        y: font_size,
+    };
+    for (idx, &count) in counts.iter().enumerate() {
+        let (i, j) = ((idx % size) as u16, (idx / size) as u16);
+        let x = (i * config.field_size + config.offset) as i32;
+        let y = (j * config.field_size + config.offset + 100) as i32;
+        let heat = count.saturating_sub(1).min(size - 1) as f32 / (size - 1).max(1) as f32;
+        let shade = 255 - ((255 - HOTTEST) as f32 * heat) as u8;
+        draw_filled_rect_mut(
+            c,
+            Rect::at(x, y).of_size(config.field_size as u32, config.field_size as u32),
+            Luma([shade]),
+        );
+        draw_text_mut(
+            c,
+            if shade < 160 { WHITE } else { BLACK },
+            x + config.field_size as i32 - font_size as i32 - config.thick as i32,
+            y + config.field_size as i32 - font_size as i32 - config.thick as i32,
+            scale,
+            font,
+            &count.to_string(),
+        );
+    }
+}
+
+/// Draws the borders of the regions as wide gray bands beneath the area
+/// borders, so both remain visible where they coincide.
+fn print_regions(c: &mut GrayImage, kenken: &KenKen, config: &DrawConfig) {
+    if kenken.regions.is_empty() {
+        return;
+    }
+    let band = 3 * config.thick as i32;
+    let shift = (band - config.thick as i32) / 2;
+    for i in 0..kenken.size {
+        for j in 0..kenken.size {
+            let x = (i * config.field_size + config.offset) as i32;
+            let y = (j * config.field_size + config.offset + 100) as i32;
+            if i + 1 < kenken.size && !kenken.same_region(&Field(i, j), &Field(i + 1, j)) {
+                draw_filled_rect_mut(
+                    c,
+                    Rect::at(x + config.field_size as i32 - shift, y)
+                        .of_size(band as u32, config.field_size as u32),
+                    REGION,
+                );
+            }
+            if j + 1 < kenken.size && !kenken.same_region(&Field(i, j), &Field(i, j + 1)) {
+                draw_filled_rect_mut(
+                    c,
+                    Rect::at(x, y + config.field_size as i32 - shift)
+                        .of_size(config.field_size as u32, band as u32),
+                    REGION,
+                );
+            }
+        }
+    }
+}
+
+/// Killer Sudokus separate the boxes by thick lines, as the cages are dashed.
+fn thick_border(kenken: &KenKen, field1: &Field, field2: &Field) -> bool {
+    if kenken.killer {
+        !kenken.same_region(field1, field2)
+    } else {
+        !kenken.same_area(field1, field2)
+    }
+}
+
+/// Outlines every area by a dashed line inset into its fields, as in Killer
+/// Sudoku. A side of a field gets a line where the neighbor belongs to another
+/// area; the line runs on into the neighboring fields of the same area.
+fn print_cages(c: &mut GrayImage, kenken: &KenKen, config: &DrawConfig) {
+    let inset = config.field_size as i32 / 10;
+    let size = config.field_size as i32;
+    let same = |x: i32, y: i32, dx: i32, dy: i32| -> bool {
+        let (nx, ny) = (x + dx, y + dy);
+        (0..kenken.size as i32).contains(&nx)
+            && (0..kenken.size as i32).contains(&ny)
+            && kenken.same_area(&Field(x as u16, y as u16), &Field(nx as u16, ny as u16))
+    };
+    // how far a line along a side reaches beyond the field, towards (dx, dy)
+    // and perpendicular to the side towards (nx, ny)
+    let reach = |x: i32, y: i32, (dx, dy): (i32, i32), (nx, ny): (i32, i32)| -> i32 {
+        match (same(x, y, dx, dy), same(x, y, dx + nx, dy + ny)) {
+            (true, true) => -inset,
+            (true, false) => 0,
+            (false, _) => inset,
+        }
+    };
+
+    for x in 0..kenken.size as i32 {
+        for y in 0..kenken.size as i32 {
+            let left = x * size + config.offset as i32;
+            let top = y * size + config.offset as i32 + 100;
+            for (nx, ny) in [(0, -1), (0, 1), (-1, 0), (1, 0)] {
+                if same(x, y, nx, ny) {
+                    continue;
+                }
+                if ny != 0 {
+                    let line_y = if ny < 0 {
+                        top + inset
+                    } else {
+                        top + size - inset
+                    };
+                    let from = left + reach(x, y, (-1, 0), (nx, ny));
+                    let to = left + size - reach(x, y, (1, 0), (nx, ny));
+                    dashed_line(c, (from, line_y), to - from, true, config);
+                } else {
+                    let line_x = if nx < 0 {
+                        left + inset
+                    } else {
+                        left + size - inset
+                    };
+                    let from = top + reach(x, y, (0, -1), (nx, ny));
+                    let to = top + size - reach(x, y, (0, 1), (nx, ny));
+                    dashed_line(c, (line_x, from), to - from, false, config);
+                }
+            }
+        }
+    }
+}
+
+fn dashed_line(
+    c: &mut GrayImage,
+    (x, y): (i32, i32),
+    length: i32,
+    horizontal: bool,
+    config: &DrawConfig,
+) {
+    let dash = (config.field_size as i32 / 20).max(4);
+    let width = config.thin as u32 + 1;
+    for start in (0..length).step_by(2 * dash as usize) {
+        let len = dash.min(length - start) as u32;
+        let rect = if horizontal {
+            Rect::at(x + start, y).of_size(len, width)
+        } else {
+            Rect::at(x, y + start).of_size(width, len)
+        };
+        draw_filled_rect_mut(c, rect, BLACK);
+    }
+}
+
 fn print_horizontal_separators(
     c: &mut GrayImage,
     kenken: &KenKen,
@@ -103,7 +340,7 @@ fn print_horizontal_separators(
     config: &DrawConfig,
 ) {
     for i in 0..kenken.size {
-        if after_row == 0 || !kenken.same_area(&Field(i, after_row - 1), &Field(i, after_row)) {
+        if after_row == 0 || thick_border(kenken, &Field(i, after_row - 1), &Field(i, after_row)) {
             draw_filled_rect_mut(
                 c,
                 Rect::at(
@@ -135,7 +372,7 @@ fn print_vertical_separators(
 ) {
     for i in 0..kenken.size {
         if after_column == 0
-            || !kenken.same_area(&Field(after_column - 1, i), &Field(after_column, i))
+            || thick_border(kenken, &Field(after_column - 1, i), &Field(after_column, i))
         {
             draw_filled_rect_mut(
                 c,
@@ -170,13 +407,20 @@ fn print_targets<'a>(
     for i in 0..kenken.size {
         for j in 0..kenken.size {
             if let Some(area) = kenken.is_id_field(Field(i, j)) {
-                let text = match area.ty {
-                    Type::Add => format!("{}{:<width$}", "+", area.solution, width = 20 - 1),
-                    Type::Mul => format!("{}{:<width$}", "*", area.solution, width = 20 - 1),
-                    Type::Div => format!("{}{:<width$}", "÷", area.solution, width = 20 - 1),
-                    Type::Sub => format!("{}{:<width$}", "-", area.solution, width = 20 - 1),
-                    Type::Single => format!("{:<width$}", area.solution, width = 20),
-                };
+                let text = kenken.label(area, glyph);
+                if kenken.killer {
+                    // break the dashed cage line behind the target
+                    let width = 0.6 * config.target_font * text.chars().count() as f32;
+                    draw_filled_rect_mut(
+                        c,
+                        Rect::at(
+                            (config.offset + i * config.field_size + config.target_x) as i32 - 4,
+                            (config.offset + j * config.field_size + config.target_y + 100) as i32,
+                        )
+                        .of_size(width as u32 + 8, config.target_font as u32),
+                        WHITE,
+                    );
+                }
                 draw_text_mut(
                     c,
                     BLACK,
@@ -190,3 +434,23 @@ fn print_targets<'a>(
         }
     }
 }
+
+/// Writes the givens large and centered in their fields.
+fn print_givens<'a>(c: &mut GrayImage, kenken: &KenKen, config: &DrawConfig, font: &'a Font<'a>) {
+    let size = config.field_size as f32 / 2.0;
+    let scale = Scale { x: size, y: size };
+    for (field, value) in &kenken.givens {
+        let text = value.to_string();
+        let width = text.len() as i32 * config.field_size as i32 / 4;
+        draw_text_mut(
+            c,
+            BLACK,
+            (config.offset + field.0 * config.field_size + config.field_size / 2) as i32
+                - width / 2,
+            (config.offset + field.1 * config.field_size + config.field_size / 3 + 100) as i32,
+            scale,
+            font,
+            &text,
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+
+use crate::{
+    kenken::{Field, KenKen},
+    sat::Encoding,
+    solve::Outcome,
+};
+
+/// Areas and houses of a puzzle which have no common solution. It is
+/// minimal unless a check timed out: without any one of them, the rest is
+/// solvable. Givens count as part of the areas they lie in.
+#[derive(Debug, PartialEq)]
+pub struct Conflict {
+    /// Indices into `KenKen::areas`.
+    pub areas: Vec<usize>,
+    /// Indices into `KenKen::houses`.
+    pub houses: Vec<usize>,
+    pub minimal: bool,
+}
+
+impl Conflict {
+    /// The fields of the conflicting areas.
+    pub fn fields(&self, kenken: &KenKen) -> Vec<Field> {
+        self.areas
+            .iter()
+            .flat_map(|&a| kenken.areas[a].fields.iter().cloned())
+            .collect()
+    }
+}
+
+/// A minimal conflict of an unsolvable puzzle, `None` if it has a solution.
+/// Starting from all constraints, each area and then each house is dropped
+/// if the rest stays unsolvable, so that the conflict has as few areas as
+/// possible: a wrong target is usually the cause. Checks which take longer
+/// than `timeout` keep their constraint.
+pub fn explain(kenken: &KenKen, timeout: Option<Duration>) -> Result<Option<Conflict>> {
+    let mut areas = vec![true; kenken.areas.len()];
+    let mut houses = vec![true; kenken.houses().len()];
+    match Encoding::new(kenken).satisfiable(timeout) {
+        Outcome::Solved(()) => return Ok(None),
+        Outcome::TimedOut => bail!("the solver timed out on the whole puzzle"),
+        Outcome::Infeasible => {}
+    }
+
+    let mut minimal = true;
+    for idx in 0..areas.len() + houses.len() {
+        *flag(&mut areas, &mut houses, idx) = false;
+        match Encoding::restricted(kenken, &areas, &houses).satisfiable(timeout) {
+            Outcome::Infeasible => {}
+            outcome => {
+                *flag(&mut areas, &mut houses, idx) = true;
+                minimal &= outcome != Outcome::TimedOut;
+            }
+        }
+    }
+
+    let marked = |flags: &[bool]| {
+        flags
+            .iter()
+            .enumerate()
+            .filter(|(_, &on)| on)
+            .map(|(i, _)| i)
+            .collect()
+    };
+    Ok(Some(Conflict {
+        areas: marked(&areas),
+        houses: marked(&houses),
+        minimal,
+    }))
+}
+
+/// The flag of the `idx`-th constraint, counting the areas before the houses.
+fn flag<'a>(areas: &'a mut [bool], houses: &'a mut [bool], idx: usize) -> &'a mut bool {
+    match idx.checked_sub(areas.len()) {
+        None => &mut areas[idx],
+        Some(h) => &mut houses[h],
+    }
+}
+
+/// The name of the `idx`-th house of `KenKen::houses`, counted from 1.
+pub fn house_name(kenken: &KenKen, idx: usize) -> String {
+    let n = kenken.size as usize;
+    let mut diagonals = vec![];
+    if kenken.diagonals.main() {
+        diagonals.push("main diagonal");
+    }
+    if kenken.diagonals.anti() {
+        diagonals.push("anti-diagonal");
+    }
+    match idx {
+        _ if idx < n => format!("row {}", idx + 1),
+        _ if idx < 2 * n => format!("column {}", idx - n + 1),
+        _ if idx < 2 * n + diagonals.len() => diagonals[idx - 2 * n].to_string(),
+        _ => format!("region {}", idx - 2 * n - diagonals.len() + 1),
+    }
+}
+
+#[cfg(test)]
+mod test_explain {
+    use super::*;
+    use crate::kenken::{Area, Type};
+
+    fn puzzle(areas: Vec<Area>) -> KenKen {
+        KenKen::new(0, 3, areas)
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut areas: Vec<Area> = (0..3)
+            .flat_map(|y| {
+                (0..3).map(move |x| {
+                    Area::new(
+                        Type::Single,
+                        (x + y) % 3 + 1,
+                        vec![Field(x as u16, y as u16)],
+                    )
+                })
+            })
+            .collect();
+        assert_eq!(explain(&puzzle(areas.clone()), None).unwrap(), None);
+
+        // a second 1 in the middle row
+        areas[4].solution = 1;
+        let kenken = puzzle(areas.clone());
+        let conflict = explain(&kenken, None).unwrap().unwrap();
+        assert!(conflict.minimal && conflict.areas.contains(&4));
+        // unsolvable, but solvable without any one of its constraints
+        let flags = |marked: &[usize], len: usize, except: Option<usize>| -> Vec<bool> {
+            (0..len)
+                .map(|i| marked.contains(&i) && Some(i) != except)
+                .collect()
+        };
+        let (num_areas, num_houses) = (kenken.areas.len(), kenken.houses().len());
+        let check = |areas: Vec<bool>, houses: Vec<bool>| {
+            Encoding::restricted(&kenken, &areas, &houses).satisfiable(None)
+        };
+        let all = check(
+            flags(&conflict.areas, num_areas, None),
+            flags(&conflict.houses, num_houses, None),
+        );
+        assert_eq!(all, Outcome::Infeasible);
+        for &a in &conflict.areas {
+            let rest = check(
+                flags(&conflict.areas, num_areas, Some(a)),
+                flags(&conflict.houses, num_houses, None),
+            );
+            assert_eq!(rest, Outcome::Solved(()));
+        }
+        for &h in &conflict.houses {
+            let rest = check(
+                flags(&conflict.areas, num_areas, None),
+                flags(&conflict.houses, num_houses, Some(h)),
+            );
+            assert_eq!(rest, Outcome::Solved(()));
+        }
+
+        // a target no values reach is a conflict on its own
+        areas[4] = Area::new(Type::Add, 20, vec![Field(1, 1), Field(2, 1)]);
+        areas.remove(5);
+        let conflict = explain(&puzzle(areas), None).unwrap().unwrap();
+        assert_eq!(
+            conflict,
+            Conflict {
+                areas: vec![4],
+                houses: vec![],
+                minimal: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_house_name() {
+        let mut kenken = puzzle(vec![]);
+        kenken.diagonals = crate::kenken::Diagonals::Anti;
+        let names: Vec<String> = (0..kenken.houses().len())
+            .map(|h| house_name(&kenken, h))
+            .collect();
+        assert_eq!(names[0], "row 1");
+        assert_eq!(names[5], "column 3");
+        assert_eq!(names[6], "anti-diagonal");
+    }
+}
@@ -1,10 +1,18 @@
 use std::vec;
 
-use rand::{prelude::{IteratorRandom, Distribution}, Rng, thread_rng};
+use anyhow::{anyhow, bail, Result};
+use rand::{
+    prelude::{Distribution, IteratorRandom, SliceRandom},
+    rngs::StdRng,
+    Rng, SeedableRng,
+};
 
 use crate::{
     asg::Assignment,
-    kenken::{Area, Field, KenKen, Type},
+    kenken::{box_regions, connected, Area, Diagonals, Field, KenKen, Symmetry, Type},
+    latin,
+    shape::{self, ShapeConfig},
+    solve::{Backend, Outcome},
 };
 
 pub struct DifficultyConfig {
@@ -12,11 +20,46 @@ pub struct DifficultyConfig {
     pub p_mul: f32,
     pub p_div: f32,
     pub p_sub: f32,
+    pub p_mod: f32,
+    pub p_min: f32,
+    pub p_max: f32,
+    pub p_gcd: f32,
+    pub p_lcm: f32,
+    pub p_concat: f32,
     pub size_factor: f32,
+    /// Constraints on the area layout, which replace `size_factor` if given.
+    /// Splitting areas for a unique solution may break them.
+    pub shapes: Option<ShapeConfig>,
+    /// Show only the targets. Such puzzles are always made unique, as the
+    /// solvers may reach a target with any operator.
+    pub hidden_ops: bool,
+    /// The values filling every row and column, `1..=size` if not given.
+    pub values: Option<Vec<i64>>,
+    pub diagonals: Diagonals,
+    /// Add random irregular regions which must also contain every value once.
+    pub jigsaw: bool,
+    /// Killer Sudoku: the boxes as regions and sum cages with distinct values.
+    pub killer: bool,
+    /// The symmetry of the area layout.
+    pub symmetry: Symmetry,
+    /// Split areas until the puzzle has a unique solution, which
+    /// `hidden_ops` implies.
+    pub unique: bool,
+    /// When making the solution unique, add givens instead of splitting areas
+    /// of at most two fields.
+    pub givens: bool,
+    /// Seed of the random generator: the same seed and configuration give
+    /// the same puzzle.
+    pub seed: u64,
 }
 
-fn add_field_biased(kenken: &mut KenKen, to_add: Field, neighbor: &Field, max_area: u16) -> bool {
-    let mut rng = rand::thread_rng();
+fn add_field_biased<R: Rng>(
+    kenken: &mut KenKen,
+    to_add: Field,
+    neighbor: &Field,
+    max_area: u16,
+    rng: &mut R,
+) -> bool {
     let area = kenken.get_area_mut(neighbor).unwrap();
     if area.size() == 1 && rng.gen_bool(0.85) {
         area.fields.push(to_add);
@@ -32,31 +75,64 @@ fn add_field_biased(kenken: &mut KenKen, to_add: Field, neighbor: &Field, max_ar
     }
 }
 
-fn random_area_gen(id: u64, size: u16, config: &DifficultyConfig) -> KenKen {
-    let mut kenken = KenKen {
+fn empty_kenken(id: u64, size: u16, config: &DifficultyConfig) -> KenKen {
+    KenKen {
         id,
         areas: vec![],
         size,
-    };
+        hidden_ops: config.hidden_ops,
+        values: config.values.clone(),
+        diagonals: config.diagonals,
+        givens: vec![],
+        regions: vec![],
+        killer: config.killer,
+        symmetry: config.symmetry,
+    }
+}
+
+/// Areas satisfying the shape constraints, or an error if none were found.
+fn shaped_area_gen<R: Rng>(
+    id: u64,
+    size: u16,
+    config: &DifficultyConfig,
+    shapes: &ShapeConfig,
+    rng: &mut R,
+) -> Result<KenKen> {
+    let mut kenken = empty_kenken(id, size, config);
+    let areas = shape::random_partition(size, shapes, config.symmetry, 1000, rng)
+        .ok_or_else(|| anyhow!("no area layout satisfies the shape constraints"))?;
+    kenken.areas = areas
+        .into_iter()
+        .map(|fields| Area::new(Type::Single, 0, fields))
+        .collect();
+    Ok(kenken)
+}
+
+fn random_area_gen<R: Rng>(id: u64, size: u16, config: &DifficultyConfig, rng: &mut R) -> KenKen {
+    let mut kenken = empty_kenken(id, size, config);
 
-    let max_area = (size as f32 * config.size_factor).ceil() as u16; 
+    let max_area = (size as f32 * config.size_factor).ceil() as u16;
 
     for i in 0..size {
         for j in 0..size {
             let mut added = false;
-            if rand::random() {
-                if j > 0 && (i > 0 || rand::random()) {
-                    added = add_field_biased(&mut kenken, Field(i, j), &Field(i, j - 1), max_area);
+            if rng.gen() {
+                if j > 0 && (i > 0 || rng.gen()) {
+                    added =
+                        add_field_biased(&mut kenken, Field(i, j), &Field(i, j - 1), max_area, rng);
                 }
                 if !added && i > 0 {
-                    added = add_field_biased(&mut kenken, Field(i, j), &Field(i - 1, j), max_area);
+                    added =
+                        add_field_biased(&mut kenken, Field(i, j), &Field(i - 1, j), max_area, rng);
                 }
             } else {
-                if i > 0 && (j > 0 || rand::random()) {
-                    added = add_field_biased(&mut kenken, Field(i, j), &Field(i - 1, j), max_area);
+                if i > 0 && (j > 0 || rng.gen()) {
+                    added =
+                        add_field_biased(&mut kenken, Field(i, j), &Field(i - 1, j), max_area, rng);
                 }
                 if !added && j > 0 {
-                    added = add_field_biased(&mut kenken, Field(i, j), &Field(i, j - 1), max_area);
+                    added =
+                        add_field_biased(&mut kenken, Field(i, j), &Field(i, j - 1), max_area, rng);
                 }
             }
             if !added {
@@ -73,105 +149,500 @@ fn random_area_gen(id: u64, size: u16, config: &DifficultyConfig) -> KenKen {
     kenken
 }
 
-fn random_solution(size: u16) -> Assignment {
+/// A uniformly random Latin square over `values`.
+fn random_solution<R: Rng>(values: &[i64], rng: &mut R) -> Assignment {
+    let square = latin::random_latin_square(values.len(), rng);
     let mut sol = Assignment::empty();
-    let mut rng = rand::thread_rng();
+    for (y, row) in square.iter().enumerate() {
+        for (x, &v) in row.iter().enumerate() {
+            sol.set(Field(x as u16, y as u16), values[v]);
+        }
+    }
+    sol
+}
 
-    for i in 0..size {
-        for j in 0..size {
-            sol.set(Field(i, j), ((i + j) % size) + 1);
+/// Random connected regions of `size` fields each. Starting from the rows, a
+/// field bordering another region is repeatedly swapped with a field of that
+/// region bordering the first, as long as both regions stay connected.
+fn random_regions<R: Rng>(size: u16, rng: &mut R) -> Vec<Vec<Field>> {
+    let fields: Vec<Field> = (0..size)
+        .flat_map(|y| (0..size).map(move |x| Field(x, y)))
+        .collect();
+    let neighbors = |f: &Field| -> Vec<usize> {
+        let mut neighbors = vec![];
+        if f.0 > 0 {
+            neighbors.push((f.1 * size + f.0 - 1) as usize);
+        }
+        if f.0 + 1 < size {
+            neighbors.push((f.1 * size + f.0 + 1) as usize);
+        }
+        if f.1 > 0 {
+            neighbors.push(((f.1 - 1) * size + f.0) as usize);
+        }
+        if f.1 + 1 < size {
+            neighbors.push(((f.1 + 1) * size + f.0) as usize);
+        }
+        neighbors
+    };
+    let members = |region: &[usize], r: usize| -> Vec<Field> {
+        fields
+            .iter()
+            .zip(region)
+            .filter(|(_, &q)| q == r)
+            .map(|(f, _)| *f)
+            .collect()
+    };
+
+    // the region of every field, by index in reading order
+    let mut region: Vec<usize> = fields.iter().map(|f| f.1 as usize).collect();
+    for _ in 0..(size as usize).pow(3) * 4 {
+        let a = rng.gen_range(0..fields.len());
+        let (ra, rb) = match neighbors(&fields[a])
+            .into_iter()
+            .map(|n| region[n])
+            .filter(|&r| r != region[a])
+            .choose(rng)
+        {
+            Some(rb) => (region[a], rb),
+            None => continue,
+        };
+        let b = (0..fields.len())
+            .filter(|&b| region[b] == rb && neighbors(&fields[b]).iter().any(|&n| region[n] == ra))
+            .choose(rng)
+            .unwrap();
+
+        region[a] = rb;
+        region[b] = ra;
+        if !connected(&members(&region, ra)) || !connected(&members(&region, rb)) {
+            region[a] = ra;
+            region[b] = rb;
         }
     }
+    (0..size as usize).map(|r| members(&region, r)).collect()
+}
 
-    for _ in 0..(size * size) {
-        let swap = (0..size).choose_multiple(&mut rng, 2);
-        if rand::random() {
-            for i in 0..size {
-                let tmp = sol.get(&Field(swap[0], i)).unwrap();
-                sol.set(Field(swap[0], i), sol.get(&Field(swap[1], i)).unwrap());
-                sol.set(Field(swap[1], i), tmp);
-            }
-        } else {
-            for i in 0..size {
-                let tmp = sol.get(&Field(i, swap[0])).unwrap();
-                sol.set(Field(i, swap[0]), sol.get(&Field(i, swap[1])).unwrap());
-                sol.set(Field(i, swap[1]), tmp);
-            }
+/// A random Latin square whose constrained diagonals and regions also hold
+/// distinct values, filled by backtracking with the values shuffled, always
+/// on the field with the fewest candidates left. `None` if none was found
+/// within `budget` steps, which may mean that none exists (e.g. for diagonals
+/// of sizes 2 and 3).
+fn random_house_solution<R: Rng>(
+    kenken: &KenKen,
+    budget: usize,
+    rng: &mut R,
+) -> Option<Assignment> {
+    let size = kenken.size as usize;
+    let houses = kenken.houses();
+    let mut cell_houses = vec![vec![]; size * size];
+    for (h, house) in houses.iter().enumerate() {
+        for f in house {
+            cell_houses[f.1 as usize * size + f.0 as usize].push(h);
         }
     }
+    let mut fill = HouseFill {
+        size,
+        cell_houses,
+        used: vec![0; houses.len()],
+        grid: vec![None; size * size],
+        budget,
+    };
+    if !fill.fill(rng) {
+        return None;
+    }
 
-    sol
+    let values = kenken.values();
+    let mut sol = Assignment::empty();
+    for (cell, v) in fill.grid.iter().enumerate() {
+        sol.set(
+            Field((cell % size) as u16, (cell / size) as u16),
+            values[v.unwrap()],
+        );
+    }
+    Some(sol)
 }
 
-fn assign_area(area: &mut Area, typ: Type, f1: u64, f2: u64) {
-    area.ty = typ;
-    match typ {
-        Type::Div => {
-            if f1 > f2 {
-                area.solution = f1 / f2;
-            } else {
-                area.solution = f2 / f1;
+/// The state of `random_house_solution`, with fields by index in reading
+/// order and values by index in the value set.
+struct HouseFill {
+    size: usize,
+    cell_houses: Vec<Vec<usize>>,
+    /// Per house: a bitmask of the values it contains.
+    used: Vec<u64>,
+    grid: Vec<Option<usize>>,
+    budget: usize,
+}
+
+impl HouseFill {
+    fn candidates(&self, cell: usize) -> u64 {
+        let used = self.cell_houses[cell]
+            .iter()
+            .fold(0, |used, &h| used | self.used[h]);
+        !used & ((1 << self.size) - 1)
+    }
+
+    fn fill<R: Rng>(&mut self, rng: &mut R) -> bool {
+        let cell = match (0..self.grid.len())
+            .filter(|&cell| self.grid[cell].is_none())
+            .min_by_key(|&cell| self.candidates(cell).count_ones())
+        {
+            Some(cell) => cell,
+            None => return true,
+        };
+        if self.budget == 0 {
+            return false;
+        }
+        self.budget -= 1;
+
+        let candidates = self.candidates(cell);
+        let mut values: Vec<usize> = (0..self.size)
+            .filter(|v| candidates & (1 << v) != 0)
+            .collect();
+        values.shuffle(rng);
+        for v in values {
+            self.grid[cell] = Some(v);
+            for &h in &self.cell_houses[cell] {
+                self.used[h] |= 1 << v;
             }
-        },
-        Type::Sub => {
-            if f1 > f2 {
-                area.solution = f1 - f2;
-            } else {
-                area.solution = f2 - f1;
+            if self.fill(rng) {
+                return true;
             }
-        },
-        Type::Add => {
-            area.solution = f1 + f2;
-        },
-        Type::Mul => {
-            area.solution = f1 * f2;
-        },
-        Type::Single => {}
-    }
-}
-
-pub fn generate(id: u64, size: u16, config: &DifficultyConfig) -> KenKen {
-
-    assert!(config.p_add + config.p_div + config.p_mul + config.p_sub == 1.0);
-
-    let mut kenken = random_area_gen(id,size, config);
-    let sol = random_solution(size);
-    let mut rng = thread_rng();
-
-    for mut area in &mut kenken.areas {
-        if area.fields.len() == 1 {
-            area.solution = sol.get(&area.fields[0]).unwrap() as u64;
-        } else if area.fields.len() == 2 {
-            let f1 = sol.get(&area.fields[0]).unwrap() as u64;
-            let f2 = sol.get(&area.fields[1]).unwrap() as u64;
-            assert_ne!(f1,f2);
-            let divisible = (f1 > f2 && f1 % f2 == 0) || (f2 > f1 && f2 % f1 == 0);
-
-            if divisible {
-                let choices = [Type::Add, Type::Sub, Type::Mul, Type::Div];
-                let dist = rand::distributions::WeightedIndex::new(&[config.p_add, config.p_sub, config.p_mul, config.p_div]).unwrap();
-                assign_area(&mut area, choices[dist.sample(&mut rng)], f1, f2);
-            } else {
-                let choices = [Type::Add, Type::Sub, Type::Mul];
-                let dist = rand::distributions::WeightedIndex::new(&[config.p_add, config.p_sub, config.p_mul]).unwrap();
-                assign_area(&mut area, choices[dist.sample(&mut rng)], f1, f2);
+            for &h in &self.cell_houses[cell] {
+                self.used[h] &= !(1 << v);
+            }
+        }
+        self.grid[cell] = None;
+        false
+    }
+}
+
+fn assign_area(area: &mut Area, typ: Type, values: &[i64]) {
+    area.ty = typ;
+    area.solution = typ.evaluate(values).unwrap();
+}
+
+pub fn generate(id: u64, size: u16, config: &DifficultyConfig) -> Result<KenKen> {
+    assert!(
+        (config.p_add
+            + config.p_div
+            + config.p_mul
+            + config.p_sub
+            + config.p_mod
+            + config.p_min
+            + config.p_max
+            + config.p_gcd
+            + config.p_lcm
+            + config.p_concat
+            - 1.0)
+            .abs()
+            < 1e-4
+    );
+
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut kenken = match &config.shapes {
+        Some(shapes) => shaped_area_gen(id, size, config, shapes, &mut rng)?,
+        // the growth by size factor cannot keep a symmetry
+        None if !config.symmetry.is_none() => {
+            let max_area = (size as f32 * config.size_factor).ceil() as usize;
+            shaped_area_gen(id, size, config, &ShapeConfig::new(1, max_area), &mut rng)?
+        }
+        None => random_area_gen(id, size, config, &mut rng),
+    };
+    if config.killer {
+        kenken.regions = box_regions(size)
+            .ok_or_else(|| anyhow!("Killer Sudoku needs a square number as size"))?;
+    }
+    let sol = if kenken.diagonals.is_none() && kenken.regions.is_empty() && !config.jigsaw {
+        random_solution(&kenken.values(), &mut rng)
+    } else {
+        // restart with new regions (or a new shuffle) when stuck
+        (0..100)
+            .find_map(|_| {
+                if config.jigsaw {
+                    kenken.regions = random_regions(size, &mut rng);
+                }
+                random_house_solution(&kenken, 1000 * (size as usize).pow(2), &mut rng)
+            })
+            .ok_or_else(|| anyhow!("no Latin square satisfies the diagonals and regions"))?
+    };
+
+    for area in &mut kenken.areas {
+        assign_random_type(area, &sol, config, &mut rng)?;
+    }
+
+    if config.killer {
+        split_repeats(&mut kenken, &sol, config, &mut rng)?;
+    }
+
+    if config.unique || config.hidden_ops {
+        make_unique(&mut kenken, &sol, config, &mut rng)?;
+    }
+
+    Ok(kenken)
+}
+
+/// Fails if no operator with a positive weight has a target for the values.
+fn assign_random_type<R: Rng>(
+    area: &mut Area,
+    sol: &Assignment,
+    config: &DifficultyConfig,
+    rng: &mut R,
+) -> Result<()> {
+    let values: Vec<i64> = area
+        .reading_order()
+        .iter()
+        .map(|f| sol.get(f).unwrap())
+        .collect();
+    if values.len() == 1 {
+        assign_area(area, Type::Single, &values);
+    } else if config.killer {
+        assign_area(area, Type::Add, &values);
+    } else {
+        let choices: Vec<(Type, f32)> = [
+            (Type::Add, config.p_add),
+            (Type::Sub, config.p_sub),
+            (Type::Mul, config.p_mul),
+            (Type::Div, config.p_div),
+            (Type::Mod, config.p_mod),
+            (Type::Min, config.p_min),
+            (Type::Max, config.p_max),
+            (Type::Gcd, config.p_gcd),
+            (Type::Lcm, config.p_lcm),
+            (Type::Concat, config.p_concat),
+        ]
+        .into_iter()
+        // the solvers try only these operators for hidden ones
+        .filter(|(typ, _)| {
+            !config.hidden_ops || matches!(typ, Type::Add | Type::Sub | Type::Mul | Type::Div)
+        })
+        .filter(|(typ, _)| typ.evaluate(&values).is_some())
+        .collect();
+        let dist = rand::distributions::WeightedIndex::new(choices.iter().map(|(_, p)| *p))
+            .map_err(|_| {
+                anyhow!(
+                    "no operator with a positive weight fits the values {:?}",
+                    values
+                )
+            })?;
+        assign_area(area, choices[dist.sample(rng)].0, &values);
+    }
+    Ok(())
+}
+
+/// Refines the areas until `sol` is the only solution: as long as another
+/// solution exists, a field where it differs from `sol` is split off its area.
+/// Terminates at the latest when all areas are singletons. Fails if the
+/// puzzle has no solution at all, as `sol` should be one.
+fn make_unique<R: Rng>(
+    kenken: &mut KenKen,
+    sol: &Assignment,
+    config: &DifficultyConfig,
+    rng: &mut R,
+) -> Result<()> {
+    let solver = Backend::Sat.solver(None);
+
+    loop {
+        let other = match solver.solutions(kenken, 2)?.0 {
+            Outcome::Solved(found) => found.into_iter().find_map(|asgs| {
+                let mut differing: Vec<Field> = asgs
+                    .iter()
+                    .flat_map(|asg| asg.iter())
+                    .filter(|(f, v)| sol.get(f) != Some(*v))
+                    .map(|(f, _)| f)
+                    .collect();
+                // in a fixed order, for the choice by seed
+                differing.sort();
+                (!differing.is_empty()).then_some(differing)
+            }),
+            outcome => bail!(
+                "the generated puzzle cannot be made unique: {:?}",
+                outcome.map(|_| ())
+            ),
+        };
+
+        let differing = match other {
+            Some(differing) => differing,
+            None => break,
+        };
+        // with givens, cages are only split if a proper cage remains
+        let splittable: Vec<Field> = differing
+            .iter()
+            .filter(|f| {
+                !config.givens
+                    || kenken
+                        .areas
+                        .iter()
+                        .any(|a| a.fields.contains(f) && a.fields.len() > 2)
+            })
+            .cloned()
+            .collect();
+        match splittable.choose(rng) {
+            Some(&field) => split_area(kenken, field, sol, config, rng)?,
+            None => {
+                let field = *differing.choose(rng).unwrap();
+                kenken.givens.push((field, sol.get(&field).unwrap()));
             }
-        } else {
-            let choices = [Type::Add, Type::Mul];
-            let dist = rand::distributions::WeightedIndex::new(&[config.p_add, config.p_mul]).unwrap();
-            if choices[dist.sample(&mut rng)] == Type::Mul {
-                area.ty = Type::Mul;
-                area.solution = area
-                    .fields
+        }
+    }
+
+    if config.givens {
+        minimize_givens(kenken, rng)?;
+    }
+    Ok(())
+}
+
+/// Drops every given that is not needed for a unique solution, so that the
+/// remaining givens are minimal.
+fn minimize_givens<R: Rng>(kenken: &mut KenKen, rng: &mut R) -> Result<()> {
+    let solver = Backend::Sat.solver(None);
+    let mut givens = kenken.givens.clone();
+    givens.shuffle(rng);
+    for given in givens {
+        kenken.givens.retain(|g| *g != given);
+        if solver.count(kenken, 2)?.0 != Outcome::Solved(1) {
+            kenken.givens.push(given);
+        }
+    }
+    Ok(())
+}
+
+/// Splits the areas until none repeats a value of `sol`, as Killer Sudoku
+/// requires.
+fn split_repeats<R: Rng>(
+    kenken: &mut KenKen,
+    sol: &Assignment,
+    config: &DifficultyConfig,
+    rng: &mut R,
+) -> Result<()> {
+    loop {
+        let repeated = kenken.areas.iter().find_map(|area| {
+            area.fields.iter().enumerate().find_map(|(i, f)| {
+                area.fields[..i]
                     .iter()
-                    .map(|f| sol.get(f).unwrap())
-                    .fold(1, |a, b| a * (b as u64));
-            } else {
-                area.ty = Type::Add;
-                area.solution = area.fields.iter().map(|f| sol.get(f).unwrap() as u64).sum();
+                    .any(|g| sol.get(g) == sol.get(f))
+                    .then_some(*f)
+            })
+        });
+        match repeated {
+            Some(field) => split_area(kenken, field, sol, config, rng)?,
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Makes `field` and its images under the layout symmetry singletons and
+/// splits the rest of their areas into connected areas.
+fn split_area<R: Rng>(
+    kenken: &mut KenKen,
+    field: Field,
+    sol: &Assignment,
+    config: &DifficultyConfig,
+    rng: &mut R,
+) -> Result<()> {
+    let isolated = kenken.symmetry.orbit(&field, kenken.size);
+    let mut parts: Vec<Vec<Field>> = isolated.iter().map(|f| vec![*f]).collect();
+    while let Some(idx) = kenken
+        .areas
+        .iter()
+        .position(|a| a.fields.iter().any(|f| isolated.contains(f)))
+    {
+        let mut rest = kenken.areas.swap_remove(idx).fields;
+        rest.retain(|f| !isolated.contains(f));
+
+        while let Some(start) = rest.pop() {
+            let mut part = vec![start];
+            let mut i = 0;
+            while i < part.len() {
+                let f = part[i];
+                let (neighbors, others): (Vec<Field>, Vec<Field>) = rest
+                    .iter()
+                    .partition(|n| f.0.abs_diff(n.0) + f.1.abs_diff(n.1) == 1);
+                part.extend(neighbors);
+                rest = others;
+                i += 1;
             }
+            parts.push(part);
         }
     }
 
-    kenken
+    for fields in parts {
+        let mut area = Area::new(Type::Single, 0, fields);
+        assign_random_type(&mut area, sol, config, rng)?;
+        kenken.areas.push(area);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_gen {
+    use super::*;
+
+    fn config(seed: u64) -> DifficultyConfig {
+        DifficultyConfig {
+            p_add: 0.2,
+            p_mul: 0.1,
+            p_div: 0.1,
+            p_sub: 0.1,
+            p_mod: 0.1,
+            p_min: 0.1,
+            p_max: 0.1,
+            p_gcd: 0.1,
+            p_lcm: 0.05,
+            p_concat: 0.05,
+            size_factor: 0.6,
+            shapes: None,
+            hidden_ops: false,
+            values: None,
+            diagonals: Diagonals::None,
+            jigsaw: false,
+            killer: false,
+            symmetry: Default::default(),
+            unique: false,
+            givens: false,
+            seed,
+        }
+    }
+
+    #[test]
+    fn test_hidden_ops_unique() {
+        let solver = Backend::Search.solver(None);
+        for seed in 0..8 {
+            let config = DifficultyConfig {
+                hidden_ops: true,
+                ..config(seed)
+            };
+            let kenken = generate(1, 4, &config).unwrap();
+            // unique without --unique
+            assert_eq!(
+                solver.count(&kenken, 2).unwrap().0,
+                Outcome::Solved(1),
+                "seed {}",
+                seed
+            );
+        }
+    }
+
+    #[test]
+    fn test_no_fitting_operator() {
+        // 1 - 1 has no positive target
+        let mut area = Area::new(Type::Add, 2, vec![Field(0, 0), Field(1, 1)]);
+        let mut sol = Assignment::empty();
+        sol.set(Field(0, 0), 1);
+        sol.set(Field(1, 1), 1);
+        let config = DifficultyConfig {
+            p_add: 0.0,
+            p_mul: 0.0,
+            p_div: 0.0,
+            p_sub: 1.0,
+            p_mod: 0.0,
+            p_min: 0.0,
+            p_max: 0.0,
+            p_gcd: 0.0,
+            p_lcm: 0.0,
+            p_concat: 0.0,
+            ..config(0)
+        };
+        assert!(
+            assign_random_type(&mut area, &sol, &config, &mut StdRng::seed_from_u64(0)).is_err()
+        );
+    }
 }
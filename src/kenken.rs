@@ -1,3 +1,4 @@
+use clap::ArgEnum;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -17,6 +18,105 @@ impl Field {
     }
 }
 
+/// Whether the fields form one orthogonally connected shape.
+pub fn connected(fields: &[Field]) -> bool {
+    let mut reached = match fields.first() {
+        Some(&first) => vec![first],
+        None => return true,
+    };
+    let mut i = 0;
+    while i < reached.len() {
+        let f = reached[i];
+        for n in fields {
+            if f.0.abs_diff(n.0) + f.1.abs_diff(n.1) == 1 && !reached.contains(n) {
+                reached.push(*n);
+            }
+        }
+        i += 1;
+    }
+    reached.len() == fields.len()
+}
+
+/// The main diagonals that must hold distinct values, like rows and columns.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone, Copy, ArgEnum)]
+pub enum Diagonals {
+    #[default]
+    None,
+    /// From the top left to the bottom right.
+    Main,
+    /// From the top right to the bottom left.
+    Anti,
+    Both,
+}
+
+impl Diagonals {
+    pub fn is_none(&self) -> bool {
+        *self == Diagonals::None
+    }
+
+    pub fn main(&self) -> bool {
+        matches!(self, Diagonals::Main | Diagonals::Both)
+    }
+
+    pub fn anti(&self) -> bool {
+        matches!(self, Diagonals::Anti | Diagonals::Both)
+    }
+}
+
+/// A symmetry of the area layout.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone, Copy, ArgEnum)]
+pub enum Symmetry {
+    #[default]
+    None,
+    /// Rotation by 180 degrees.
+    Rot180,
+    /// Rotation by 90 degrees, and so by 180 and 270 degrees.
+    Rot90,
+    /// Reflection at the vertical center line.
+    Mirror,
+}
+
+impl Symmetry {
+    pub fn is_none(&self) -> bool {
+        *self == Symmetry::None
+    }
+
+    /// The number of transformations, including the identity.
+    pub fn order(&self) -> usize {
+        match self {
+            Symmetry::None => 1,
+            Symmetry::Rot180 | Symmetry::Mirror => 2,
+            Symmetry::Rot90 => 4,
+        }
+    }
+
+    /// The image of `field` under the `k`-th transformation, where 0 is the
+    /// identity and the rotations turn clockwise by `k` quarters (halves).
+    pub fn image(&self, k: usize, field: &Field, size: u16) -> Field {
+        let n = size - 1;
+        let Field(x, y) = *field;
+        match (self, k % self.order()) {
+            (_, 0) => *field,
+            (Symmetry::Rot90, 1) => Field(n - y, x),
+            (Symmetry::Rot90, 3) => Field(y, n - x),
+            (Symmetry::Mirror, _) => Field(n - x, y),
+            _ => Field(n - x, n - y),
+        }
+    }
+
+    /// The distinct images of `field`, starting with itself.
+    pub fn orbit(&self, field: &Field, size: u16) -> Vec<Field> {
+        let mut orbit = vec![];
+        for k in 0..self.order() {
+            let image = self.image(k, field, size);
+            if !orbit.contains(&image) {
+                orbit.push(image);
+            }
+        }
+        orbit
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy)]
 pub enum Type {
     Mul,
@@ -24,17 +124,108 @@ pub enum Type {
     Add,
     Div,
     Single,
+    /// The larger of two values modulo the smaller one.
+    Mod,
+    Min,
+    Max,
+    Gcd,
+    Lcm,
+    /// The decimal digits of the values in reading order, see `Area::reading_order`.
+    Concat,
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl Type {
+    /// The target of an area of this type whose fields have `values`, if it is
+    /// valid. `Sub` and `Div` take the largest value minus (divided by) all
+    /// others and must be positive; for `Concat`, `values` must be in reading
+    /// order, without negative values or a leading zero, so that the digits
+    /// of every prefix start the target.
+    pub fn evaluate(&self, values: &[i64]) -> Option<i64> {
+        let (largest_idx, &largest) = values.iter().enumerate().max_by_key(|(_, &v)| v)?;
+        let others = values
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != largest_idx)
+            .map(|(_, &v)| v);
+        match self {
+            Type::Add => values.iter().try_fold(0i64, |a, &b| a.checked_add(b)),
+            Type::Mul => values.iter().try_fold(1i64, |a, &b| a.checked_mul(b)),
+            Type::Sub => largest.checked_sub(others.sum()).filter(|&sol| sol > 0),
+            Type::Div => {
+                let product: i64 = others.product();
+                if product != 0 && largest % product == 0 {
+                    Some(largest / product).filter(|&sol| sol > 0)
+                } else {
+                    None
+                }
+            }
+            Type::Single => Some(values[0]).filter(|_| values.len() == 1),
+            Type::Mod => {
+                let smallest = *values.iter().min()?;
+                if values.len() == 2 && smallest > 0 {
+                    Some(largest % smallest)
+                } else {
+                    None
+                }
+            }
+            Type::Min => values.iter().min().cloned(),
+            Type::Max => Some(largest),
+            Type::Gcd => Some(values.iter().fold(0, |a, &b| gcd(a, b))),
+            Type::Lcm => values.iter().try_fold(1i64, |a, &b| {
+                if b == 0 {
+                    return None;
+                }
+                (a / gcd(a, b)).checked_mul(b.abs())
+            }),
+            Type::Concat
+                if values.iter().any(|&v| v < 0) || (values.len() > 1 && values[0] == 0) =>
+            {
+                None
+            }
+            Type::Concat => values
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<String>()
+                .parse()
+                .ok(),
+        }
+    }
+
+    /// The symbol of the operator in printed puzzles.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Type::Add => "+",
+            Type::Mul => "*",
+            Type::Div => "/",
+            Type::Sub => "-",
+            Type::Single => "",
+            Type::Mod => "%",
+            Type::Min => "min",
+            Type::Max => "max",
+            Type::Gcd => "gcd",
+            Type::Lcm => "lcm",
+            Type::Concat => "&",
+        }
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Area {
     pub ty: Type,
-    pub solution: u64,
+    pub solution: i64,
     pub fields: Vec<Field>,
 }
 
 impl Area {
-    pub fn new(ty: Type, solution: u64, fields: Vec<Field>) -> Self {
+    pub fn new(ty: Type, solution: i64, fields: Vec<Field>) -> Self {
         Area {
             ty,
             solution,
@@ -46,6 +237,13 @@ impl Area {
         self.fields.len() as u16
     }
 
+    /// The fields sorted top to bottom, then left to right.
+    pub fn reading_order(&self) -> Vec<Field> {
+        let mut fields = self.fields.clone();
+        fields.sort_by_key(|f| (f.1, f.0));
+        fields
+    }
+
     fn min_y_field(&self) -> u16 {
         self.fields.iter().map(|f| f.1).min().unwrap()
     }
@@ -68,20 +266,191 @@ impl Area {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct KenKen {
     pub id: u64,
     pub areas: Vec<Area>,
     pub size: u16,
+    /// Variant in which only the targets are shown: the operator of an area may
+    /// be any that reaches its target.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hidden_ops: bool,
+    /// The values filling every row and column, `1..=size` if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub values: Option<Vec<i64>>,
+    #[serde(default, skip_serializing_if = "Diagonals::is_none")]
+    pub diagonals: Diagonals,
+    /// Pre-filled fields and their values.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub givens: Vec<(Field, i64)>,
+    /// Jigsaw variant: irregular regions of `size` fields each, which must
+    /// contain every value once in addition to the rows and columns.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub regions: Vec<Vec<Field>>,
+    /// Killer Sudoku: the values of an area are distinct, and the areas are
+    /// drawn as dashed cages.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub killer: bool,
+    /// The symmetry of the area layout.
+    #[serde(default, skip_serializing_if = "Symmetry::is_none")]
+    pub symmetry: Symmetry,
+}
+
+/// The standard values `1..=size`.
+pub fn default_values(size: u16) -> Vec<i64> {
+    (1..=size as i64).collect()
+}
+
+/// The boxes of a Sudoku, for sizes that are perfect squares.
+pub fn box_regions(size: u16) -> Option<Vec<Vec<Field>>> {
+    let side = (1..=size).find(|s| s * s >= size)?;
+    if side * side != size {
+        return None;
+    }
+    let boxes = (0..size)
+        .map(|b| {
+            let (x0, y0) = (b % side * side, b / side * side);
+            (y0..y0 + side)
+                .flat_map(|y| (x0..x0 + side).map(move |x| Field(x, y)))
+                .collect()
+        })
+        .collect();
+    Some(boxes)
+}
+
+/// Parses a value set given as comma separated values and inclusive ranges,
+/// e.g. `0..=5` or `-3..=-1,1..=3`.
+pub fn parse_values(s: &str) -> Result<Vec<i64>, String> {
+    let mut values = vec![];
+    for part in s.split(',').map(str::trim) {
+        let parse = |v: &str| {
+            v.trim()
+                .parse::<i64>()
+                .map_err(|_| format!("invalid value '{}'", v))
+        };
+        match part.split_once("..=") {
+            Some((from, to)) => values.extend(parse(from)?..=parse(to)?),
+            None => values.push(parse(part)?),
+        }
+    }
+    values.sort_unstable();
+    values.dedup();
+    Ok(values)
 }
 
 impl KenKen {
+    /// A puzzle of the plain variant: the values `1..=size` once in every row
+    /// and column, and no further constraints.
+    pub fn new(id: u64, size: u16, areas: Vec<Area>) -> Self {
+        KenKen {
+            id,
+            areas,
+            size,
+            hidden_ops: false,
+            values: None,
+            diagonals: Diagonals::None,
+            givens: vec![],
+            regions: vec![],
+            killer: false,
+            symmetry: Symmetry::None,
+        }
+    }
+
+    /// The operator and target of an area, with `symbol` giving the symbol
+    /// of an operator. Hidden operators and Killer Sudoku show no operator.
+    pub fn label(&self, area: &Area, symbol: fn(Type) -> &'static str) -> String {
+        let op = if self.hidden_ops || self.killer {
+            ""
+        } else {
+            symbol(area.ty)
+        };
+        format!("{}{}", op, area.solution)
+    }
+
+    /// The value set of the puzzle, sorted ascending.
+    pub fn values(&self) -> Vec<i64> {
+        match &self.values {
+            Some(values) => {
+                let mut values = values.clone();
+                values.sort_unstable();
+                values
+            }
+            None => default_values(self.size),
+        }
+    }
+
+    pub fn given(&self, field: &Field) -> Option<i64> {
+        self.givens
+            .iter()
+            .find(|(f, _)| f == field)
+            .map(|(_, v)| *v)
+    }
+
+    pub fn on_main_diagonal(&self, field: &Field) -> bool {
+        self.diagonals.main() && field.0 == field.1
+    }
+
+    pub fn on_anti_diagonal(&self, field: &Field) -> bool {
+        self.diagonals.anti() && field.0 + field.1 + 1 == self.size
+    }
+
+    pub fn same_region(&self, field1: &Field, field2: &Field) -> bool {
+        self.regions
+            .iter()
+            .any(|region| region.contains(field1) && region.contains(field2))
+    }
+
+    /// Whether two fields lie in a common row, column, constrained diagonal or region.
+    pub fn same_house(&self, field1: &Field, field2: &Field) -> bool {
+        field1.equal_axis(field2)
+            || (self.on_main_diagonal(field1) && self.on_main_diagonal(field2))
+            || (self.on_anti_diagonal(field1) && self.on_anti_diagonal(field2))
+            || self.same_region(field1, field2)
+    }
+
+    /// The rows, columns, constrained diagonals and regions, each containing every value once.
+    pub fn houses(&self) -> Vec<Vec<Field>> {
+        let n = self.size;
+        let mut houses: Vec<Vec<Field>> = (0..n)
+            .map(|y| (0..n).map(|x| Field(x, y)).collect())
+            .collect();
+        houses.extend((0..n).map(|x| (0..n).map(|y| Field(x, y)).collect()));
+        if self.diagonals.main() {
+            houses.push((0..n).map(|i| Field(i, i)).collect());
+        }
+        if self.diagonals.anti() {
+            houses.push((0..n).map(|i| Field(n - 1 - i, i)).collect());
+        }
+        houses.extend(self.regions.iter().cloned());
+        houses
+    }
+
     pub fn same_area(&self, field1: &Field, field2: &Field) -> bool {
         self.areas
             .iter()
             .any(|area| area.fields.contains(field1) && area.fields.contains(field2))
     }
 
+    /// Whether the image of every area under each transformation of the
+    /// symmetry is an area.
+    pub fn symmetric_layout(&self) -> bool {
+        self.areas.iter().all(|area| {
+            (1..self.symmetry.order()).all(|k| {
+                let mut image: Vec<Field> = area
+                    .fields
+                    .iter()
+                    .map(|f| self.symmetry.image(k, f, self.size))
+                    .collect();
+                image.sort();
+                self.areas.iter().any(|other| {
+                    let mut fields = other.fields.clone();
+                    fields.sort();
+                    fields == image
+                })
+            })
+        })
+    }
+
     pub fn is_id_field<'a>(&'a self, field: Field) -> Option<&'a Area> {
         self.areas.iter().find_map(|a| a.id_field(field))
     }
@@ -102,7 +471,7 @@ impl KenKen {
     pub fn total_number_of_assignments(&self) -> usize {
         self.areas
             .iter()
-            .map(|area| area.possible_assignments(self.size).len())
+            .map(|area| self.possible_assignments(area).len())
             .sum()
     }
 
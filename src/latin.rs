@@ -0,0 +1,177 @@
+use rand::{prelude::SliceRandom, Rng};
+
+/// A Latin square as its incidence cube: `cube[(row, col, symbol)]` is 1 if
+/// the cell holds the symbol. During the Jacobson–Matthews walk one entry
+/// may be -1, which makes the cube improper.
+struct Cube {
+    size: usize,
+    cube: Vec<i8>,
+    improper: Option<(usize, usize, usize)>,
+}
+
+impl Cube {
+    /// The cyclic square `(row + col) % size`.
+    fn cyclic(size: usize) -> Self {
+        let mut cube = Cube {
+            size,
+            cube: vec![0; size * size * size],
+            improper: None,
+        };
+        for r in 0..size {
+            for c in 0..size {
+                cube.add(r, c, (r + c) % size, 1);
+            }
+        }
+        cube
+    }
+
+    fn idx(&self, r: usize, c: usize, s: usize) -> usize {
+        (r * self.size + c) * self.size + s
+    }
+
+    fn get(&self, r: usize, c: usize, s: usize) -> i8 {
+        self.cube[self.idx(r, c, s)]
+    }
+
+    fn add(&mut self, r: usize, c: usize, s: usize, delta: i8) {
+        let idx = self.idx(r, c, s);
+        self.cube[idx] += delta;
+    }
+
+    /// The positions along one axis of the cube, through the other two
+    /// coordinates, where the entry is 1.
+    fn ones<F: Fn(usize) -> i8>(&self, entry: F) -> Vec<usize> {
+        (0..self.size).filter(|&i| entry(i) == 1).collect()
+    }
+
+    /// One move of the chain. From a proper cube it starts at a random 0 entry,
+    /// from an improper one at the -1 entry, choosing randomly among the two
+    /// 1 entries on each line through it.
+    fn step<R: Rng>(&mut self, rng: &mut R) {
+        let (r, c, s) = match self.improper {
+            Some(cell) => cell,
+            None => loop {
+                let (r, c, s) = (
+                    rng.gen_range(0..self.size),
+                    rng.gen_range(0..self.size),
+                    rng.gen_range(0..self.size),
+                );
+                if self.get(r, c, s) == 0 {
+                    break (r, c, s);
+                }
+            },
+        };
+        let r2 = *self.ones(|i| self.get(i, c, s)).choose(rng).unwrap();
+        let c2 = *self.ones(|i| self.get(r, i, s)).choose(rng).unwrap();
+        let s2 = *self.ones(|i| self.get(r, c, i)).choose(rng).unwrap();
+
+        self.add(r, c, s, 1);
+        self.add(r, c2, s2, 1);
+        self.add(r2, c, s2, 1);
+        self.add(r2, c2, s, 1);
+        self.add(r, c, s2, -1);
+        self.add(r, c2, s, -1);
+        self.add(r2, c, s, -1);
+        self.add(r2, c2, s2, -1);
+
+        self.improper = (self.get(r2, c2, s2) == -1).then_some((r2, c2, s2));
+    }
+
+    fn square(&self) -> Vec<Vec<usize>> {
+        (0..self.size)
+            .map(|r| {
+                (0..self.size)
+                    .map(|c| (0..self.size).find(|&s| self.get(r, c, s) == 1).unwrap())
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// A uniformly random Latin square of the symbols `0..size`, by rows, from the
+/// Markov chain of Jacobson and Matthews. The chain starts at the cyclic square
+/// and runs until it has visited `size³` proper cubes. Only proper cubes are
+/// counted: stopping at the first proper cube after a fixed number of moves
+/// would favor squares ending long improper excursions.
+pub fn random_latin_square<R: Rng>(size: usize, rng: &mut R) -> Vec<Vec<usize>> {
+    let mut cube = Cube::cyclic(size);
+    if size < 2 {
+        return cube.square();
+    }
+    let mut proper = 0;
+    while proper < size.pow(3) {
+        cube.step(rng);
+        if cube.improper.is_none() {
+            proper += 1;
+        }
+    }
+    cube.square()
+}
+
+#[cfg(test)]
+mod test_latin {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn is_latin(square: &[Vec<usize>]) -> bool {
+        let n = square.len();
+        let distinct = |values: Vec<usize>| {
+            let mut values = values;
+            values.sort_unstable();
+            values == (0..n).collect::<Vec<usize>>()
+        };
+        (0..n).all(|i| {
+            distinct(square[i].clone()) && distinct((0..n).map(|r| square[r][i]).collect())
+        })
+    }
+
+    /// The number of 2x2 subsquares, an isotopy invariant.
+    fn intercalates(square: &[Vec<usize>]) -> usize {
+        let n = square.len();
+        let mut count = 0;
+        for r1 in 0..n {
+            for r2 in r1 + 1..n {
+                for c1 in 0..n {
+                    for c2 in c1 + 1..n {
+                        if square[r1][c1] == square[r2][c2] && square[r1][c2] == square[r2][c1] {
+                            count += 1;
+                        }
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn test_latin_squares() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for size in 1..=9 {
+            assert!(is_latin(&random_latin_square(size, &mut rng)));
+        }
+    }
+
+    #[test]
+    fn test_isotopy_classes() {
+        // of the 576 squares of size 4, 144 are isotopic to the Klein group
+        // table with 12 intercalates, the other 432 to the cyclic one with 4
+        let mut rng = StdRng::seed_from_u64(2);
+        let klein = (0..400)
+            .filter(|_| intercalates(&random_latin_square(4, &mut rng)) == 12)
+            .count();
+        assert!(
+            (60..=140).contains(&klein),
+            "{} of 400 Klein squares",
+            klein
+        );
+
+        // both isotopy classes of size 5 are reached, not only the cyclic one
+        // without intercalates
+        let mut counts: Vec<usize> = (0..200)
+            .map(|_| intercalates(&random_latin_square(5, &mut rng)))
+            .collect();
+        counts.sort_unstable();
+        counts.dedup();
+        assert_eq!(counts.len(), 2, "{:?}", counts);
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::ErrorKind,
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::kenken::KenKen;
+
+/// A collection of puzzles: `puzzle<id>.ron` files in `data_dir`, their
+/// drawings `puzzle<id>.png` in `image_dir` and an index of all of them in
+/// `data_dir/index.ron`.
+///
+/// Ids are reserved by creating the puzzle file exclusively, so generators
+/// running at the same time never share one; the index is rewritten under a
+/// lock file.
+pub struct Library {
+    pub data_dir: PathBuf,
+    pub image_dir: PathBuf,
+}
+
+/// The index entry of one puzzle.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Entry {
+    pub id: u64,
+    pub size: u16,
+    /// Total number of possible area assignments, larger for harder puzzles.
+    pub difficulty: usize,
+    /// Seed of the generator, if the puzzle was generated.
+    pub seed: Option<u64>,
+    /// Seconds since the Unix epoch.
+    pub created: u64,
+    /// How the puzzle was made, e.g. `generate` or `batch-generate`.
+    pub source: String,
+}
+
+impl Entry {
+    pub fn new(kenken: &KenKen, seed: Option<u64>, source: &str) -> Self {
+        Entry {
+            id: kenken.id,
+            size: kenken.size,
+            difficulty: kenken.total_number_of_assignments(),
+            seed,
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            source: source.to_string(),
+        }
+    }
+}
+
+/// Removes the index lock when dropped.
+struct Lock(PathBuf);
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+impl Library {
+    pub fn new(data_dir: impl Into<PathBuf>, image_dir: impl Into<PathBuf>) -> Self {
+        Library {
+            data_dir: data_dir.into(),
+            image_dir: image_dir.into(),
+        }
+    }
+
+    pub fn puzzle_path(&self, id: u64) -> PathBuf {
+        self.data_dir.join(format!("puzzle{}.ron", id))
+    }
+
+    pub fn image_path(&self, id: u64) -> PathBuf {
+        self.image_dir.join(format!("puzzle{}.png", id))
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.data_dir.join("index.ron")
+    }
+
+    /// Reserves the next free id by creating an empty puzzle file for it,
+    /// which `add` fills in. Creating the file fails if it exists, so two
+    /// processes cannot reserve the same id.
+    pub fn reserve(&self) -> Result<u64> {
+        fs::create_dir_all(&self.data_dir)?;
+        let mut id = self.last_id()? + 1;
+        loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(self.puzzle_path(id))
+            {
+                Ok(_) => return Ok(id),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => id += 1,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Frees an id reserved for a puzzle that was not made after all.
+    pub fn release(&self, id: u64) -> Result<()> {
+        fs::remove_file(self.puzzle_path(id))?;
+        Ok(())
+    }
+
+    /// Writes the puzzle to its reserved file and adds it to the index.
+    pub fn add(&self, kenken: &KenKen, entry: Entry) -> Result<()> {
+        fs::write(self.puzzle_path(kenken.id), ron::to_string(kenken)?)?;
+        let _lock = self.lock()?;
+        let mut entries = self.entries()?;
+        entries.retain(|e| e.id != entry.id);
+        entries.push(entry);
+        entries.sort_by_key(|e| e.id);
+        self.write_index(&entries)
+    }
+
+    pub fn load(&self, id: u64) -> Result<KenKen> {
+        let path = self.puzzle_path(id);
+        let input = fs::read_to_string(&path)
+            .with_context(|| format!("no puzzle {} in {}", id, self.data_dir.display()))?;
+        Ok(ron::from_str(&input)?)
+    }
+
+    pub fn entry(&self, id: u64) -> Result<Entry> {
+        self.entries()?
+            .into_iter()
+            .find(|e| e.id == id)
+            .ok_or_else(|| {
+                anyhow!(
+                    "no puzzle {} in the index of {}",
+                    id,
+                    self.data_dir.display()
+                )
+            })
+    }
+
+    /// Deletes the puzzle, its drawing and its index entry.
+    pub fn remove(&self, id: u64) -> Result<()> {
+        let _lock = self.lock()?;
+        let mut entries = self.entries()?;
+        let before = entries.len();
+        entries.retain(|e| e.id != id);
+        let path = self.puzzle_path(id);
+        if entries.len() == before && !path.exists() {
+            bail!("no puzzle {} in {}", id, self.data_dir.display());
+        }
+        for path in [path, self.image_path(id)] {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        self.write_index(&entries)
+    }
+
+    /// The index entries by id. Without an index, as for a directory of
+    /// puzzles from before the library, the entries are read off the puzzle
+    /// files, with `existing` as source.
+    pub fn entries(&self) -> Result<Vec<Entry>> {
+        match fs::read_to_string(self.index_path()) {
+            Ok(input) => Ok(ron::from_str(&input)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => self.scan(),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn scan(&self) -> Result<Vec<Entry>> {
+        let mut entries = vec![];
+        for (id, path) in self.puzzle_files()? {
+            // skip ids reserved by a generator still running
+            let input = fs::read_to_string(&path)?;
+            if input.is_empty() {
+                continue;
+            }
+            let kenken: KenKen =
+                ron::from_str(&input).with_context(|| format!("cannot read {}", path.display()))?;
+            let mut entry = Entry::new(&kenken, None, "existing");
+            entry.id = id;
+            if let Ok(modified) = fs::metadata(&path)?.modified() {
+                entry.created = modified
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs());
+            }
+            entries.push(entry);
+        }
+        entries.sort_by_key(|e| e.id);
+        Ok(entries)
+    }
+
+    /// The ids and paths of all `puzzle<id>.ron` files.
+    fn puzzle_files(&self) -> Result<Vec<(u64, PathBuf)>> {
+        let mut files = vec![];
+        if !self.data_dir.exists() {
+            return Ok(files);
+        }
+        for entry in fs::read_dir(&self.data_dir)? {
+            let path = entry?.path();
+            let id = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| {
+                    name.strip_prefix("puzzle")?
+                        .strip_suffix(".ron")?
+                        .parse::<u64>()
+                        .ok()
+                });
+            if let Some(id) = id {
+                files.push((id, path));
+            }
+        }
+        Ok(files)
+    }
+
+    fn last_id(&self) -> Result<u64> {
+        let files = self.puzzle_files()?.into_iter().map(|(id, _)| id).max();
+        let indexed = match fs::read_to_string(self.index_path()) {
+            Ok(input) => ron::from_str::<Vec<Entry>>(&input)?
+                .iter()
+                .map(|e| e.id)
+                .max(),
+            Err(_) => None,
+        };
+        Ok(files.max(indexed).unwrap_or(0))
+    }
+
+    /// Replaces the index at once, so that readers never see half of it.
+    fn write_index(&self, entries: &[Entry]) -> Result<()> {
+        let tmp = self.data_dir.join("index.ron.tmp");
+        fs::write(
+            &tmp,
+            ron::ser::to_string_pretty(entries, Default::default())?,
+        )?;
+        fs::rename(tmp, self.index_path())?;
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<Lock> {
+        let path = self.data_dir.join("index.lock");
+        let start = Instant::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(Lock(path)),
+                Err(e)
+                    if e.kind() == ErrorKind::AlreadyExists
+                        && start.elapsed() < Duration::from_secs(10) =>
+                {
+                    thread::sleep(Duration::from_millis(10))
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    bail!(
+                        "{} is locked; remove it if no generator is running",
+                        path.display()
+                    )
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// The date `YYYY-MM-DD` of seconds since the Unix epoch, in UTC.
+pub fn date(secs: u64) -> String {
+    // civil from days, after Howard Hinnant
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod test_library {
+    use super::*;
+    use crate::kenken::{Area, Field, Type};
+
+    #[test]
+    fn test_library() {
+        let dir = std::env::temp_dir().join(format!("kenken-library-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let library = Library::new(&dir, &dir);
+
+        let first = library.reserve().unwrap();
+        let second = library.reserve().unwrap();
+        assert_eq!((first, second), (1, 2));
+        library.release(first).unwrap();
+
+        let kenken = KenKen::new(
+            second,
+            1,
+            vec![Area::new(Type::Single, 1, vec![Field(0, 0)])],
+        );
+        library
+            .add(&kenken, Entry::new(&kenken, Some(7), "test"))
+            .unwrap();
+        assert_eq!(library.entry(second).unwrap().seed, Some(7));
+        assert_eq!(library.load(second).unwrap().id, second);
+        // the id after the largest one in use, not after the count of files
+        assert_eq!(library.reserve().unwrap(), 3);
+
+        library.remove(second).unwrap();
+        assert!(library.entries().unwrap().is_empty());
+        assert!(library.remove(second).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(1_709_164_800), "2024-02-29");
+    }
+}
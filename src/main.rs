@@ -1,88 +1,523 @@
-use anyhow::Result;
+use analysis::Stage;
+use anyhow::{bail, Result};
 use clap::{Parser, Subcommand};
+use draw::DrawConfig;
 use gen::DifficultyConfig;
-use kenken::KenKen;
+use kenken::{Diagonals, KenKen, Symmetry, Type};
+use library::{Entry, Library};
 use parse::parse;
-use solve::solve;
-use std::{fs::read_to_string, path::PathBuf};
-use validate::Validator;
+use print::PrintConfig;
+use rand::{rngs::StdRng, SeedableRng};
+use shape::ShapeConfig;
+use solve::{Backend, Outcome};
 use std::fs;
+use std::{
+    fs::read_to_string,
+    path::{Path, PathBuf},
+    process,
+    time::Duration,
+};
+use transform::Transform;
+use validate::Validator;
 
+mod analysis;
 mod asg;
+mod batch;
+mod canon;
 mod draw;
+mod explain;
 mod gen;
 mod kenken;
+mod latin;
+mod library;
+mod minimize;
+mod mzn;
 mod parse;
 mod print;
+mod sat;
+mod search;
+mod shape;
 mod solve;
+mod transform;
 mod validate;
 
 fn main() -> Result<()> {
     let args = Cli::parse();
 
     match args.command {
-        Commands::Solve { path, verbose } => {
-            let input = read_to_string(path)?;
-            let kenken: KenKen = ron::from_str(&input)?;
-            let sol = solve(&kenken);
-            print::print(&kenken, sol, 10)?;
+        Commands::Solve {
+            path,
+            verbose,
+            solver,
+            count,
+            emit_lp,
+            timeout,
+            hex,
+        } => {
+            let kenken = read_kenken(&path)?;
+            if let Some(emit_lp) = emit_lp {
+                solve::write_model(&kenken, &emit_lp)?;
+            }
+            let solver = solver.solver(timeout);
+            if let Some(limit) = count {
+                let (outcome, stats) = solver.count(&kenken, limit)?;
+                if verbose {
+                    println!("{}: {}", solver.name(), stats);
+                }
+                match outcome {
+                    Outcome::Solved(solutions) if solutions >= limit => {
+                        println!("At least {} solutions", solutions)
+                    }
+                    Outcome::Solved(solutions) => println!("{} solutions", solutions),
+                    _ => exit_unsolved(&outcome),
+                }
+            } else {
+                let (outcome, stats) = solver.solve(&kenken)?;
+                if verbose {
+                    println!("{}: {}", solver.name(), stats);
+                }
+                match outcome {
+                    Outcome::Solved(sol) => {
+                        print::print(&kenken, sol, &PrintConfig::for_kenken(&kenken, hex))?
+                    }
+                    _ => exit_unsolved(&outcome),
+                }
+            }
         }
         Commands::Generate {
             size,
-            add, sub, mul, div,
+            add,
+            sub,
+            mul,
+            div,
+            modulo,
+            min,
+            max,
+            gcd,
+            lcm,
+            concat,
             size_factor,
+            min_area,
+            max_area,
+            area_sizes,
+            max_singletons,
+            no_straight,
+            no_l_shapes,
+            no_squares,
+            symmetry,
+            hidden_ops,
+            values,
+            diagonals,
+            jigsaw,
+            killer,
+            unique,
+            givens,
+            seed,
+            data_dir,
+            image_dir,
         } => {
-            // let mut wtr = csv::Writer::from_path("results.csv")?;
-            // wtr.write_record(&["size", "type", "asgs"])?;
-            // for size_f in 5..9 {
-            //     for type_f in 1..10 {
-            //         for _ in 0..10 {
-            //             println!("{} {}", size_f, type_f);
-            //             let size_factor = size_f as f32 / 10.0;
-            //             let type_factor = type_f as f32 / 10.0;
-            //             let kenken = gen::generate(size, &DifficultyConfig { size_factor, type_factor });
-            //             let asgs = kenken.total_number_of_assignments();
-            //             wtr.write_record(&[size_factor.to_string(), type_factor.to_string(), asgs.to_string()])?;
-            //         }
-            //     }
-            // }
-
-            // wtr.flush()?;
-
-            let id = fs::read_dir("knkns")?.count() as u64 + 1;
+            let values = values
+                .map(|values| kenken::parse_values(&values).map_err(anyhow::Error::msg))
+                .transpose()?;
+            if let Some(values) = &values {
+                if values.len() != size as usize {
+                    bail!("{} values given for a grid of size {}", values.len(), size);
+                }
+            }
+
+            if !diagonals.is_none() && (size == 2 || (size == 3 && diagonals == Diagonals::Both)) {
+                bail!(
+                    "no Latin square of size {} satisfies {:?} diagonals",
+                    size,
+                    diagonals
+                );
+            }
+
+            if killer && kenken::box_regions(size).is_none() {
+                bail!("Killer Sudoku needs a square number as size, not {}", size);
+            }
+
+            let shapes = if min_area.is_some()
+                || max_area.is_some()
+                || area_sizes.is_some()
+                || max_singletons.is_some()
+                || no_straight
+                || no_l_shapes
+                || no_squares
+            {
+                let default_max = (size as f32 * size_factor).ceil() as usize;
+                let mut shapes =
+                    ShapeConfig::new(min_area.unwrap_or(1), max_area.unwrap_or(default_max));
+                if let Some(area_sizes) = area_sizes {
+                    shapes.size_weights =
+                        ShapeConfig::parse_weights(&area_sizes).map_err(anyhow::Error::msg)?;
+                }
+                shapes.max_singletons = max_singletons;
+                shapes.straight = !no_straight;
+                shapes.l_shapes = !no_l_shapes;
+                shapes.squares = !no_squares;
+                shapes.check().map_err(anyhow::Error::msg)?;
+                Some(shapes)
+            } else {
+                None
+            };
+
+            let library = Library::new(data_dir, image_dir);
+            let seed = seed.unwrap_or_else(rand::random);
 
             let gen_config = DifficultyConfig {
                 size_factor,
+                shapes,
+                symmetry,
                 p_add: add,
                 p_sub: sub,
                 p_mul: mul,
                 p_div: div,
+                p_mod: modulo,
+                p_min: min,
+                p_max: max,
+                p_gcd: gcd,
+                p_lcm: lcm,
+                p_concat: concat,
+                hidden_ops,
+                values,
+                diagonals,
+                jigsaw,
+                killer,
+                unique,
+                givens,
+                seed,
+            };
+            let id = library.reserve()?;
+            let kenken = match gen::generate(id, size, &gen_config) {
+                Ok(kenken) => kenken,
+                Err(e) => {
+                    library.release(id)?;
+                    return Err(e);
+                }
             };
-            let kenken = gen::generate(
-                id,
-                size,
-                &gen_config,
-            );
 
-            let content = ron::to_string(&kenken)?;
-            std::fs::write(format!("knkns_data/puzzle{}.ron", kenken.id), content)?;
-            draw::draw(&kenken, &PathBuf::from(format!("knkns/puzzle{}.png", kenken.id)), &draw::DEFAULT_CONFIG, Some(&gen_config))?;
-            
+            library.add(&kenken, Entry::new(&kenken, Some(seed), "generate"))?;
+            fs::create_dir_all(&library.image_dir)?;
+            draw::draw(
+                &kenken,
+                &library.image_path(kenken.id),
+                &DrawConfig::scaled(&kenken),
+                Some(&gen_config),
+            )?;
+            println!("Generated puzzle {} with seed {}", kenken.id, seed);
         }
-        Commands::Print { path } => {
-            let input = read_to_string(path)?;
-            let kenken: KenKen = ron::from_str(&input)?;
-            print::print(&kenken, vec![], 10)?;
+        Commands::Library {
+            data_dir,
+            image_dir,
+            command,
+        } => {
+            let library = Library::new(data_dir, image_dir);
+            match command {
+                LibraryCommand::List => {
+                    println!(
+                        "{:>6} {:>4} {:>10} {:>20} {:>10}  source",
+                        "id", "size", "difficulty", "seed", "created"
+                    );
+                    for entry in library.entries()? {
+                        let seed = entry.seed.map_or("-".to_string(), |seed| seed.to_string());
+                        println!(
+                            "{:>6} {:>4} {:>10} {:>20} {:>10}  {}",
+                            entry.id,
+                            entry.size,
+                            entry.difficulty,
+                            seed,
+                            library::date(entry.created),
+                            entry.source
+                        );
+                    }
+                }
+                LibraryCommand::Show { id, hex } => {
+                    let entry = library.entry(id)?;
+                    let kenken = library.load(id)?;
+                    println!(
+                        "Puzzle {} ({}), created {}",
+                        entry.id,
+                        entry.source,
+                        library::date(entry.created)
+                    );
+                    println!("size {}, difficulty {}", entry.size, entry.difficulty);
+                    if let Some(seed) = entry.seed {
+                        println!("seed {}", seed);
+                    }
+                    print::print(&kenken, vec![], &PrintConfig::for_kenken(&kenken, hex))?;
+                }
+                LibraryCommand::Remove { id } => library.remove(id)?,
+            }
+        }
+        Commands::BatchGenerate {
+            output,
+            sizes,
+            size_factors,
+            add,
+            sub,
+            mul,
+            div,
+            repeat,
+            unique,
+            solver,
+            timeout,
+            metrics,
+            jobs,
+        } => {
+            let parse = |s: &str| batch::parse_range(s).map_err(anyhow::Error::msg);
+            let sizes = kenken::parse_values(&sizes)
+                .map_err(anyhow::Error::msg)?
+                .into_iter()
+                .map(|size| match u16::try_from(size) {
+                    Ok(size) if size > 0 => Ok(size),
+                    _ => Err(anyhow::anyhow!("invalid size {}", size)),
+                })
+                .collect::<Result<Vec<u16>>>()?;
+            let config = batch::BatchConfig {
+                sizes,
+                size_factors: parse(&size_factors)?,
+                add: parse(&add)?,
+                sub: parse(&sub)?,
+                mul: parse(&mul)?,
+                div: parse(&div)?,
+                repeat,
+                unique,
+                solver,
+                timeout,
+            };
+            if [&config.add, &config.sub, &config.mul, &config.div]
+                .iter()
+                .any(|weights| weights.iter().any(|&w| w < 0.0))
+            {
+                bail!("operator weights must not be negative");
+            }
+
+            let library = Library::new(&output, &output);
+            let mut pool = rayon::ThreadPoolBuilder::new();
+            if let Some(jobs) = jobs {
+                pool = pool.num_threads(jobs);
+            }
+            let mut results = vec![];
+            let mut failed = 0;
+            for result in pool.build()?.install(|| batch::run(&config, &library)) {
+                match result {
+                    Ok(row) => results.push(row),
+                    Err(e) => {
+                        eprintln!("Job failed: {:#}", e);
+                        failed += 1;
+                    }
+                }
+            }
+
+            batch::write_metrics(&results, &metrics)?;
+            println!(
+                "Generated {} puzzles in {}, metrics in {}",
+                results.len(),
+                output.display(),
+                metrics.display()
+            );
+            if failed > 0 {
+                println!("{} jobs failed", failed);
+            }
+        }
+        Commands::Dedupe { dirs } => {
+            let (paths, puzzles): (Vec<PathBuf>, Vec<KenKen>) =
+                canon::read_puzzles(&dirs)?.into_iter().unzip();
+            let groups = canon::duplicates(&puzzles);
+            for group in &groups {
+                let names: Vec<String> = group
+                    .iter()
+                    .map(|&idx| paths[idx].display().to_string())
+                    .collect();
+                println!("{}", names.join(" = "));
+            }
+            if groups.is_empty() {
+                println!("No duplicates among {} puzzles", puzzles.len());
+            }
+        }
+        Commands::Transform {
+            path,
+            transform,
+            output,
+            solve,
+        } => {
+            let kenken = read_kenken(&path)?;
+            let image = kenken.transformed(transform)?;
+            let content = ron::to_string(&image)?;
+            if let Some(output) = output {
+                std::fs::write(output, content)?;
+            } else {
+                println!("{}", content);
+            }
+            if solve {
+                let (outcome, _) = Backend::Search.solver(None).solve(&kenken)?;
+                match outcome {
+                    Outcome::Solved(sol) => {
+                        let sol = sol
+                            .iter()
+                            .map(|asg| transform.assignment(asg, kenken.size))
+                            .collect();
+                        print::print(&image, sol, &PrintConfig::for_kenken(&image, false))?
+                    }
+                    _ => exit_unsolved(&outcome),
+                }
+            }
+        }
+        Commands::Minimize {
+            path,
+            output,
+            max_area,
+            check_timeout,
+            seed,
+        } => {
+            let kenken = read_kenken(&path)?;
+            let mut rng = StdRng::seed_from_u64(seed.unwrap_or_else(rand::random));
+            let minimized = minimize::minimize(&kenken, max_area, Some(check_timeout), &mut rng)?;
+            if let Some(output) = output {
+                std::fs::write(output, ron::to_string(&minimized)?)?;
+            }
+            print::print(
+                &minimized,
+                vec![],
+                &PrintConfig::for_kenken(&minimized, false),
+            )?;
+            println!(
+                "areas: {} -> {}, assignments: {} -> {}",
+                kenken.areas.len(),
+                minimized.areas.len(),
+                kenken.total_number_of_assignments(),
+                minimized.total_number_of_assignments()
+            );
+        }
+        Commands::Analyze {
+            path,
+            timeout,
+            heatmap,
+            stage,
+        } => {
+            let kenken = read_kenken(&path)?;
+            let analysis = analysis::analyze(&kenken, timeout)?;
+            let size = kenken.size as usize;
+            for (stage, title) in [
+                (Stage::Cage, "Candidates by the areas alone:"),
+                (Stage::Propagation, "Candidates after propagation:"),
+                (Stage::Search, "Candidates in solutions:"),
+            ] {
+                println!("{}", title);
+                for row in analysis.counts(stage).chunks(size) {
+                    println!(
+                        "{}",
+                        row.iter().map(|n| format!("{:>3}", n)).collect::<String>()
+                    );
+                }
+            }
+            println!(
+                "Forced fields: {} by the areas, {} more by propagation, {} more only by search",
+                analysis.forced(Stage::Cage),
+                analysis.forced(Stage::Propagation),
+                analysis.forced(Stage::Search)
+            );
+            if !analysis.exact {
+                println!("Some checks timed out, so fewer candidates may remain after search.");
+            }
+            println!("Assignments per area:");
+            for (area, count) in kenken.areas.iter().zip(&analysis.assignments) {
+                let first = &area.reading_order()[0];
+                println!(
+                    "{:>8} at ({}, {}), {} fields: {}",
+                    kenken.label(area, Type::symbol),
+                    first.0,
+                    first.1,
+                    area.fields.len(),
+                    count
+                );
+            }
+            if let Some(heatmap) = heatmap {
+                let mut config = DrawConfig::scaled(&kenken);
+                config.heatmap = Some(analysis.counts(stage));
+                draw::draw(&kenken, &heatmap, &config, None)?;
+            }
+        }
+        Commands::Explain { path, timeout, hex } => {
+            let kenken = read_kenken(&path)?;
+            match explain::explain(&kenken, timeout)? {
+                None => println!("The puzzle has a solution."),
+                Some(conflict) => {
+                    let mut config = PrintConfig::for_kenken(&kenken, hex);
+                    config.highlight = conflict.fields(&kenken);
+                    print::print(&kenken, vec![], &config)?;
+                    println!("No solution satisfies these areas together with the rules of");
+                    let houses: Vec<String> = conflict
+                        .houses
+                        .iter()
+                        .map(|&h| explain::house_name(&kenken, h))
+                        .collect();
+                    println!(
+                        "  {}",
+                        if houses.is_empty() {
+                            "no row or column".to_string()
+                        } else {
+                            houses.join(", ")
+                        }
+                    );
+                    if !conflict.minimal {
+                        println!("Some checks timed out, so a smaller set may conflict as well.");
+                    }
+                    process::exit(2);
+                }
+            }
+        }
+        Commands::Print { path, hex } => {
+            let kenken = read_kenken(&path)?;
+            print::print(&kenken, vec![], &PrintConfig::for_kenken(&kenken, hex))?;
         }
         Commands::Draw { path } => {
-            let input = read_to_string(path)?;
-            let kenken: KenKen = ron::from_str(&input)?;
+            let kenken = read_kenken(&path)?;
             //draw::draw(&kenken, "test.png".into(), &draw::DEFAULT_CONFIG)?;
         }
         Commands::Validate { path } => {
             let input = read_to_string(path)?;
             let kenken: KenKen = ron::from_str(&input)?;
-            kenken.validate().unwrap();
+            if let Err(errors) = kenken.validate() {
+                for error in errors {
+                    println!("{}", error);
+                }
+                process::exit(1);
+            }
+        }
+        Commands::ExportCnf { path, output } => {
+            let kenken = read_kenken(&path)?;
+            let encoding = sat::Encoding::new(&kenken);
+            let comments = encoding.comments(&kenken);
+            if let Some(output) = output {
+                let mut file = std::io::BufWriter::new(fs::File::create(output)?);
+                encoding.cnf.write_dimacs(&mut file, &comments)?;
+            } else {
+                encoding
+                    .cnf
+                    .write_dimacs(&mut std::io::stdout().lock(), &comments)?;
+            }
+        }
+        Commands::ImportModel { path, model } => {
+            let kenken = read_kenken(&path)?;
+            let encoding = sat::Encoding::new(&kenken);
+            let sol = encoding.import(&kenken, &read_to_string(model)?)?;
+            print::print(&kenken, sol, &PrintConfig::for_kenken(&kenken, false))?;
+        }
+        Commands::ExportMzn { path, output } => {
+            let kenken = read_kenken(&path)?;
+            let model = mzn::to_minizinc(&kenken);
+            if let Some(output) = output {
+                std::fs::write(output, model)?;
+            } else {
+                print!("{}", model);
+            }
+        }
+        Commands::ImportMzn { path, solution } => {
+            let kenken = read_kenken(&path)?;
+            let sol = mzn::parse_solution(&kenken, &read_to_string(solution)?)?;
+            print::print(&kenken, sol, &PrintConfig::for_kenken(&kenken, false))?;
         }
         Commands::Save { input, output } => {
             let game = parse(&input)?;
@@ -92,12 +527,41 @@ fn main() -> Result<()> {
             } else {
                 std::fs::write(format!("kenken{}.ron", game.id), content)?;
             }
-            print::print(&game, vec![], 10)?;
+            print::print(&game, vec![], &PrintConfig::for_kenken(&game, false))?;
         }
     }
     Ok(())
 }
 
+/// Exit codes: 2 if the puzzle has no solution, 3 if the solver timed out.
+/// Reads a puzzle and checks its value set, which every command relies on.
+fn read_kenken(path: &Path) -> Result<KenKen> {
+    let kenken: KenKen = ron::from_str(&read_to_string(path)?)?;
+    if let Err(error) = kenken.validate_values() {
+        bail!("invalid puzzle: {}", error);
+    }
+    Ok(kenken)
+}
+
+/// Parses a number of seconds, which must be finite and not negative.
+fn parse_seconds(s: &str) -> Result<Duration> {
+    Ok(Duration::try_from_secs_f64(s.parse()?)?)
+}
+
+fn exit_unsolved<T>(outcome: &Outcome<T>) {
+    match outcome {
+        Outcome::Infeasible => {
+            eprintln!("The puzzle has no solution; `kenken explain` shows why.");
+            process::exit(2);
+        }
+        Outcome::TimedOut => {
+            eprintln!("The solver timed out before finishing.");
+            process::exit(3);
+        }
+        Outcome::Solved(_) => {}
+    }
+}
+
 #[derive(Parser, Debug)]
 struct Cli {
     #[clap(subcommand)]
@@ -112,6 +576,25 @@ enum Commands {
 
         #[clap(short, long)]
         verbose: bool,
+
+        #[clap(long, arg_enum, default_value = "mip")]
+        solver: Backend,
+
+        /// Count solutions up to the given number instead of printing one
+        #[clap(long)]
+        count: Option<usize>,
+
+        /// Write the MIP model to an LP file (MPS if the extension is `.mps`)
+        #[clap(long, parse(from_os_str))]
+        emit_lp: Option<PathBuf>,
+
+        /// Give up after the given number of seconds
+        #[clap(long, parse(try_from_str = parse_seconds))]
+        timeout: Option<Duration>,
+
+        /// Show the values 10 to 35 as letters, from A for 10
+        #[clap(long)]
+        hex: bool,
     },
     Generate {
         size: u16,
@@ -124,9 +607,227 @@ enum Commands {
         mul: f32,
         #[clap(short, long, default_value = "0.25")]
         div: f32,
+        #[clap(long, default_value = "0")]
+        modulo: f32,
+        #[clap(long, default_value = "0")]
+        min: f32,
+        #[clap(long, default_value = "0")]
+        max: f32,
+        #[clap(long, default_value = "0")]
+        gcd: f32,
+        #[clap(long, default_value = "0")]
+        lcm: f32,
+        #[clap(long, default_value = "0")]
+        concat: f32,
 
         #[clap(long, default_value = "0.5")]
         size_factor: f32,
+
+        /// Smallest area size; any of the area layout options replaces --size-factor
+        #[clap(long)]
+        min_area: Option<usize>,
+
+        /// Largest area size, size * size-factor rounded up by default
+        #[clap(long)]
+        max_area: Option<usize>,
+
+        /// Relative frequencies of area sizes as size:weight pairs, e.g. 2:3,3:2,4:1
+        #[clap(long)]
+        area_sizes: Option<String>,
+
+        /// Largest number of single-field areas
+        #[clap(long)]
+        max_singletons: Option<usize>,
+
+        /// Forbid areas of three or more fields in one row or column
+        #[clap(long)]
+        no_straight: bool,
+
+        /// Forbid L-shaped areas of three or more fields
+        #[clap(long)]
+        no_l_shapes: bool,
+
+        /// Forbid areas containing a 2x2 block
+        #[clap(long)]
+        no_squares: bool,
+
+        /// Symmetry of the area layout
+        #[clap(long, arg_enum, default_value = "none")]
+        symmetry: Symmetry,
+
+        /// Show only the targets, not the operators; implies --unique
+        #[clap(long)]
+        hidden_ops: bool,
+
+        /// The values of the grid as values and inclusive ranges, e.g. 0..=5 or -3..=-1,1..=3
+        #[clap(long, allow_hyphen_values = true)]
+        values: Option<String>,
+
+        /// Diagonals which must also contain every value once
+        #[clap(long, arg_enum, default_value = "none")]
+        diagonals: Diagonals,
+
+        /// Add irregular regions which must also contain every value once
+        #[clap(long)]
+        jigsaw: bool,
+
+        /// Killer Sudoku: boxes as regions and sum areas without repeated values
+        #[clap(long, conflicts_with = "jigsaw")]
+        killer: bool,
+
+        /// Split areas until the solution is unique
+        #[clap(long)]
+        unique: bool,
+
+        /// With --unique, add givens rather than split areas of two fields
+        #[clap(long, requires = "unique")]
+        givens: bool,
+
+        /// Seed of the random generator, random if not given
+        #[clap(long)]
+        seed: Option<u64>,
+
+        /// Directory for the puzzle files and the index
+        #[clap(long, parse(from_os_str), default_value = "knkns_data")]
+        data_dir: PathBuf,
+
+        /// Directory for the drawings of the puzzles
+        #[clap(long, parse(from_os_str), default_value = "knkns")]
+        image_dir: PathBuf,
+    },
+    /// Browse the generated puzzles
+    Library {
+        #[clap(long, parse(from_os_str), default_value = "knkns_data")]
+        data_dir: PathBuf,
+
+        #[clap(long, parse(from_os_str), default_value = "knkns")]
+        image_dir: PathBuf,
+
+        #[clap(subcommand)]
+        command: LibraryCommand,
+    },
+    /// Generate puzzles for every combination of the given parameters and
+    /// record metrics of each
+    BatchGenerate {
+        /// Directory for the puzzles and their index, created if missing
+        #[clap(parse(from_os_str))]
+        output: PathBuf,
+
+        /// Grid sizes as values and inclusive ranges, e.g. 4..=7
+        #[clap(long, default_value = "6")]
+        sizes: String,
+
+        /// Size factors as values and stepped inclusive ranges, e.g. 0.3..=0.8:0.1
+        #[clap(long, default_value = "0.5")]
+        size_factors: String,
+
+        /// Relative weights of the operators, each as values and stepped ranges
+        #[clap(long, default_value = "1")]
+        add: String,
+        #[clap(long, default_value = "1")]
+        sub: String,
+        #[clap(long, default_value = "1")]
+        mul: String,
+        #[clap(long, default_value = "1")]
+        div: String,
+
+        /// Number of puzzles for each combination
+        #[clap(long, default_value = "1")]
+        repeat: usize,
+
+        /// Split areas until the solution is unique
+        #[clap(long)]
+        unique: bool,
+
+        /// Solver whose time is recorded
+        #[clap(long, arg_enum, default_value = "search")]
+        solver: Backend,
+
+        /// Give up solving after the given number of seconds
+        #[clap(long, parse(try_from_str = parse_seconds))]
+        timeout: Option<Duration>,
+
+        /// Metrics file, JSON if it ends in .json and CSV otherwise
+        #[clap(long, parse(from_os_str))]
+        metrics: PathBuf,
+
+        /// Number of threads, one per core by default
+        #[clap(long)]
+        jobs: Option<usize>,
+    },
+    /// Report puzzles which are the same up to rotation, reflection or
+    /// transposition
+    Dedupe {
+        /// Directories of puzzles to compare with each other
+        #[clap(parse(from_os_str), default_values = &["knkns_data", "old"])]
+        dirs: Vec<PathBuf>,
+    },
+    /// Rotate, reflect or transpose a puzzle
+    Transform {
+        #[clap(parse(from_os_str))]
+        path: PathBuf,
+
+        #[clap(arg_enum)]
+        transform: Transform,
+
+        #[clap(parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Also print the transformed puzzle with the image of the solution
+        #[clap(long)]
+        solve: bool,
+    },
+    /// Merge areas and weaken operators as long as the solution stays unique
+    Minimize {
+        #[clap(parse(from_os_str))]
+        path: PathBuf,
+
+        #[clap(parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Largest size of a merged area
+        #[clap(long, default_value = "5")]
+        max_area: usize,
+
+        /// Seconds after which a change counts as leaving several solutions
+        #[clap(long, default_value = "1", parse(try_from_str = parse_seconds))]
+        check_timeout: Duration,
+
+        /// Seed for the order in which changes are tried, random if not given
+        #[clap(long)]
+        seed: Option<u64>,
+    },
+    /// Count the candidates of every field by the areas alone, after
+    /// propagation and in solutions, and the assignments of every area
+    Analyze {
+        #[clap(parse(from_os_str))]
+        path: PathBuf,
+
+        /// Give up on a check after the given number of seconds
+        #[clap(long, parse(try_from_str = parse_seconds))]
+        timeout: Option<Duration>,
+
+        /// Draw the candidate counts of a stage as a heatmap to this PNG file
+        #[clap(long, parse(from_os_str))]
+        heatmap: Option<PathBuf>,
+
+        /// The stage shown in the heatmap
+        #[clap(long, arg_enum, default_value = "propagation")]
+        stage: Stage,
+    },
+    /// Show a smallest set of areas, rows and columns which cannot all be
+    /// satisfied, for a puzzle without solution
+    Explain {
+        #[clap(parse(from_os_str))]
+        path: PathBuf,
+
+        /// Give up on a check after the given number of seconds
+        #[clap(long, parse(try_from_str = parse_seconds))]
+        timeout: Option<Duration>,
+
+        /// Show the values 10 to 35 as letters, from A for 10
+        #[clap(long)]
+        hex: bool,
     },
     Validate {
         #[clap(parse(from_os_str))]
@@ -135,11 +836,47 @@ enum Commands {
     Print {
         #[clap(parse(from_os_str))]
         path: PathBuf,
+
+        /// Show the values 10 to 35 as letters, from A for 10
+        #[clap(long)]
+        hex: bool,
     },
     Draw {
         #[clap(parse(from_os_str))]
         path: PathBuf,
     },
+    /// Write the CNF encoding of a puzzle in DIMACS format
+    ExportCnf {
+        #[clap(parse(from_os_str))]
+        path: PathBuf,
+
+        #[clap(parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Read the model found by an external SAT solver for `export-cnf`
+    ImportModel {
+        #[clap(parse(from_os_str))]
+        path: PathBuf,
+
+        #[clap(parse(from_os_str))]
+        model: PathBuf,
+    },
+    /// Write a puzzle as a MiniZinc model
+    ExportMzn {
+        #[clap(parse(from_os_str))]
+        path: PathBuf,
+
+        #[clap(parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Read the output of `minizinc` for a model written by `export-mzn`
+    ImportMzn {
+        #[clap(parse(from_os_str))]
+        path: PathBuf,
+
+        #[clap(parse(from_os_str))]
+        solution: PathBuf,
+    },
     Save {
         input: String,
 
@@ -147,3 +884,19 @@ enum Commands {
         output: Option<PathBuf>,
     },
 }
+
+#[derive(Debug, Subcommand)]
+enum LibraryCommand {
+    /// List the puzzles in the index
+    List,
+    /// Show the index entry and the grid of a puzzle
+    Show {
+        id: u64,
+
+        /// Show the values 10 to 35 as letters, from A for 10
+        #[clap(long)]
+        hex: bool,
+    },
+    /// Delete a puzzle, its drawing and its index entry
+    Remove { id: u64 },
+}
@@ -0,0 +1,238 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use rand::{prelude::SliceRandom, Rng};
+
+use crate::{
+    asg::Assignment,
+    kenken::{Area, Field, KenKen, Symmetry, Type},
+    solve::{Backend, Outcome, Solver},
+};
+
+/// A change to try on a puzzle, by index of its areas.
+enum Change {
+    Merge(usize, usize),
+    Retype(usize, Type),
+}
+
+/// Makes a uniquely solvable puzzle harder while its solution stays unique:
+/// adjacent areas are merged into areas of up to `max_area` fields, single
+/// fields first, and operators are replaced by ones which leave more
+/// assignments. Each change is kept only if the solution is still unique,
+/// which counts as not unique if the check takes longer than `timeout`: the
+/// last changes before a puzzle turns ambiguous are the slowest to check.
+/// The changes are tried in random order until none is left. The layout
+/// symmetry is dropped once merging breaks it.
+pub fn minimize<R: Rng>(
+    kenken: &KenKen,
+    max_area: usize,
+    timeout: Option<Duration>,
+    rng: &mut R,
+) -> Result<KenKen> {
+    let solver = Backend::Search.solver(None);
+    let check = Backend::Search.solver(timeout);
+    let sol = match solver.solutions(kenken, 2)?.0 {
+        Outcome::Solved(found) if found.len() == 1 => {
+            let mut sol = Assignment::empty();
+            for (f, v) in found[0].iter().flat_map(|asg| asg.iter()) {
+                sol.set(f, v);
+            }
+            sol
+        }
+        Outcome::Solved(_) => bail!("the puzzle has more than one solution"),
+        _ => bail!("the puzzle has no solution"),
+    };
+
+    // the operators to choose from, those of the puzzle and the classic sum and product
+    let mut types = vec![Type::Add];
+    if !kenken.killer {
+        types.push(Type::Mul);
+        for area in &kenken.areas {
+            if area.ty != Type::Single && !types.contains(&area.ty) {
+                types.push(area.ty);
+            }
+        }
+    }
+
+    let mut kenken = kenken.clone();
+    loop {
+        let mut merges = merges(&kenken, max_area);
+        merges.shuffle(rng);
+        // stable, so that merges of single fields come first in random order
+        merges.sort_by_key(|&(a, b)| {
+            kenken.areas[a]
+                .fields
+                .len()
+                .min(kenken.areas[b].fields.len())
+                > 1
+        });
+        let mut retypes = retypes(&kenken, &types);
+        retypes.shuffle(rng);
+
+        let changes = merges
+            .into_iter()
+            .map(|(a, b)| Change::Merge(a, b))
+            .chain(retypes.into_iter().map(|(a, ty)| Change::Retype(a, ty)));
+        let mut improved = None;
+        for change in changes {
+            improved = variants(&kenken, &change, &sol, &types)
+                .into_iter()
+                .find(|variant| unique(check.as_ref(), variant));
+            if improved.is_some() {
+                break;
+            }
+        }
+        match improved {
+            Some(improved) => kenken = improved,
+            None => break,
+        }
+    }
+
+    if kenken.symmetry != Symmetry::None && !kenken.symmetric_layout() {
+        kenken.symmetry = Symmetry::None;
+    }
+    Ok(kenken)
+}
+
+fn unique(solver: &dyn Solver, kenken: &KenKen) -> bool {
+    matches!(solver.count(kenken, 2), Ok((Outcome::Solved(1), _)))
+}
+
+fn adjacent(a: &Area, b: &Area) -> bool {
+    a.fields.iter().any(|f| {
+        b.fields
+            .iter()
+            .any(|g| f.0.abs_diff(g.0) + f.1.abs_diff(g.1) == 1)
+    })
+}
+
+/// The pairs of adjacent areas with at most `max_area` fields together.
+fn merges(kenken: &KenKen, max_area: usize) -> Vec<(usize, usize)> {
+    let mut pairs = vec![];
+    for (i, a) in kenken.areas.iter().enumerate() {
+        for (j, b) in kenken.areas.iter().enumerate().skip(i + 1) {
+            if a.fields.len() + b.fields.len() <= max_area && adjacent(a, b) {
+                pairs.push((i, j));
+            }
+        }
+    }
+    pairs
+}
+
+fn assignments(kenken: &KenKen, area: &Area) -> usize {
+    kenken.possible_assignments(area).len()
+}
+
+/// The values of `sol` on the fields in reading order.
+fn values(fields: &[Field], sol: &Assignment) -> Vec<i64> {
+    let mut fields = fields.to_vec();
+    fields.sort_by_key(|f| (f.1, f.0));
+    fields.iter().map(|f| sol.get(f).unwrap()).collect()
+}
+
+/// The areas whose operator may be replaced, with each other operator of
+/// `types`; `variants` keeps only those leaving more assignments.
+fn retypes(kenken: &KenKen, types: &[Type]) -> Vec<(usize, Type)> {
+    if kenken.hidden_ops {
+        return vec![];
+    }
+    let mut retypes = vec![];
+    for (idx, area) in kenken.areas.iter().enumerate() {
+        if area.fields.len() < 2 {
+            continue;
+        }
+        retypes.extend(
+            types
+                .iter()
+                .filter(|&&ty| ty != area.ty)
+                .map(|&ty| (idx, ty)),
+        );
+    }
+    retypes
+}
+
+/// The puzzles resulting from a change, the least informative first: a
+/// merged area gets each operator that reaches a target, by decreasing
+/// number of assignments.
+fn variants(kenken: &KenKen, change: &Change, sol: &Assignment, types: &[Type]) -> Vec<KenKen> {
+    let (removed, fields) = match *change {
+        Change::Merge(a, b) => {
+            let mut fields = kenken.areas[a].fields.clone();
+            fields.extend(&kenken.areas[b].fields);
+            (vec![a, b], fields)
+        }
+        Change::Retype(a, _) => (vec![a], kenken.areas[a].fields.clone()),
+    };
+    let values = values(&fields, sol);
+    if kenken.killer && (1..values.len()).any(|i| values[..i].contains(&values[i])) {
+        return vec![];
+    }
+    let candidates: Vec<Type> = match *change {
+        Change::Merge(..) => types.to_vec(),
+        Change::Retype(_, ty) => vec![ty],
+    };
+    let before = match *change {
+        Change::Merge(..) => 0,
+        Change::Retype(a, _) => assignments(kenken, &kenken.areas[a]),
+    };
+
+    let mut scored: Vec<(usize, Area)> = candidates
+        .into_iter()
+        .filter_map(|ty| Some(Area::new(ty, ty.evaluate(&values)?, fields.clone())))
+        .map(|area| (assignments(kenken, &area), area))
+        .filter(|(count, _)| *count > before)
+        .collect();
+    scored.sort_by_key(|(count, _)| std::cmp::Reverse(*count));
+
+    scored
+        .into_iter()
+        .map(|(_, area)| {
+            let mut variant = kenken.clone();
+            for &idx in removed.iter().rev() {
+                variant.areas.remove(idx);
+            }
+            variant.areas.push(area);
+            variant
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test_minimize {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_minimize() {
+        // a single field for every field of the cyclic square of size 4
+        let areas = (0..4)
+            .flat_map(|y| {
+                (0..4).map(move |x| {
+                    Area::new(Type::Single, (x + y) as i64 % 4 + 1, vec![Field(x, y)])
+                })
+            })
+            .collect();
+        let kenken = KenKen::new(0, 4, areas);
+        let mut rng = StdRng::seed_from_u64(1);
+        let minimized = minimize(&kenken, 3, None, &mut rng).unwrap();
+
+        assert!(unique(Backend::Sat.solver(None).as_ref(), &minimized));
+        assert!(minimized.areas.len() < 16);
+        assert!(minimized.areas.iter().all(|area| area.fields.len() <= 3));
+        // no merge of two areas keeps the solution unique
+        let mut sol = Assignment::empty();
+        for area in &kenken.areas {
+            sol.set(area.fields[0], area.solution);
+        }
+        for (a, b) in merges(&minimized, 3) {
+            for variant in variants(
+                &minimized,
+                &Change::Merge(a, b),
+                &sol,
+                &[Type::Add, Type::Mul],
+            ) {
+                assert!(!unique(Backend::Sat.solver(None).as_ref(), &variant));
+            }
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::fmt::Write;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    asg::Assignment,
+    kenken::{default_values, Area, Field, KenKen, Type},
+};
+
+const SEPARATOR: &str = "----------";
+
+fn cell(field: &Field) -> String {
+    format!("grid[{},{}]", field.1 + 1, field.0 + 1)
+}
+
+/// With hidden operators, any operator reaching the target is allowed.
+fn hidden_constraint(area: &Area, values: &[i64]) -> String {
+    if area.fields.len() == 1 {
+        return constraint(
+            &Area::new(Type::Single, area.solution, area.fields.clone()),
+            values,
+        );
+    }
+    let options: Vec<String> = [Type::Add, Type::Sub, Type::Mul, Type::Div]
+        .into_iter()
+        .map(|ty| {
+            format!(
+                "({})",
+                constraint(&Area::new(ty, area.solution, area.fields.clone()), values)
+            )
+        })
+        .collect();
+    options.join(" \\/ ")
+}
+
+fn constraint(area: &Area, values: &[i64]) -> String {
+    let cells: Vec<String> = area.fields.iter().map(cell).collect();
+    match area.ty {
+        Type::Add => format!("sum([{}]) = {}", cells.join(", "), area.solution),
+        Type::Mul => format!("product([{}]) = {}", cells.join(", "), area.solution),
+        // the closed forms below assume positive values
+        Type::Div | Type::Mod if values.iter().any(|&v| v <= 0) => table(area, values),
+        Type::Sub if cells.len() == 2 => {
+            format!("abs({} - {}) = {}", cells[0], cells[1], area.solution)
+        }
+        Type::Div if cells.len() == 2 => format!(
+            "({a} = {t} * {b} \\/ {b} = {t} * {a})",
+            a = cells[0],
+            b = cells[1],
+            t = area.solution
+        ),
+        // the largest value minus (divided by) all others
+        Type::Sub => format!(
+            "2 * max([{c}]) - sum([{c}]) = {}",
+            area.solution,
+            c = cells.join(", ")
+        ),
+        Type::Div => format!(
+            "max([{c}]) * max([{c}]) = {} * product([{c}])",
+            area.solution,
+            c = cells.join(", ")
+        ),
+        Type::Single => format!("{} = {}", cells[0], area.solution),
+        Type::Mod => format!(
+            "max({a}, {b}) mod min({a}, {b}) = {}",
+            area.solution,
+            a = cells[0],
+            b = cells[1]
+        ),
+        Type::Min => format!("min([{}]) = {}", cells.join(", "), area.solution),
+        Type::Max => format!("max([{}]) = {}", cells.join(", "), area.solution),
+        Type::Gcd | Type::Lcm | Type::Concat => table(area, values),
+    }
+}
+
+/// Lists the possible assignments of the area as allowed tuples.
+fn table(area: &Area, values: &[i64]) -> String {
+    let rows: Vec<String> = area
+        .possible_assignments(values)
+        .iter()
+        .map(|asg| {
+            let values: Vec<String> = area
+                .fields
+                .iter()
+                .map(|f| asg.get(f).unwrap().to_string())
+                .collect();
+            values.join(", ")
+        })
+        .collect();
+    if rows.is_empty() {
+        return "false".to_string();
+    }
+    let cells: Vec<String> = area.fields.iter().map(cell).collect();
+    format!("table([{}], [| {} |])", cells.join(", "), rows.join(" | "))
+}
+
+/// Writes `kenken` as a MiniZinc model whose output is the grid, one row per line.
+pub fn to_minizinc(kenken: &KenKen) -> String {
+    let mut model = String::new();
+    writeln!(model, "% KenKen {}", kenken.id).unwrap();
+    writeln!(model, "include \"alldifferent.mzn\";").unwrap();
+    writeln!(model, "include \"table.mzn\";\n").unwrap();
+    writeln!(model, "int: n = {};", kenken.size).unwrap();
+    let values = kenken.values();
+    if values == default_values(kenken.size) {
+        writeln!(model, "array[1..n, 1..n] of var 1..n: grid;\n").unwrap();
+    } else {
+        let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        writeln!(
+            model,
+            "array[1..n, 1..n] of var {{{}}}: grid;\n",
+            values.join(", ")
+        )
+        .unwrap();
+    }
+    writeln!(
+        model,
+        "constraint forall(r in 1..n)(alldifferent(row(grid, r)));"
+    )
+    .unwrap();
+    writeln!(
+        model,
+        "constraint forall(c in 1..n)(alldifferent(col(grid, c)));"
+    )
+    .unwrap();
+    if kenken.diagonals.main() {
+        writeln!(model, "constraint alldifferent([grid[i,i] | i in 1..n]);").unwrap();
+    }
+    if kenken.diagonals.anti() {
+        writeln!(
+            model,
+            "constraint alldifferent([grid[i,n+1-i] | i in 1..n]);"
+        )
+        .unwrap();
+    }
+    for region in &kenken.regions {
+        let cells: Vec<String> = region.iter().map(cell).collect();
+        writeln!(model, "constraint alldifferent([{}]);", cells.join(", ")).unwrap();
+    }
+    if kenken.killer {
+        for area in kenken.areas.iter().filter(|area| area.fields.len() > 1) {
+            let cells: Vec<String> = area.fields.iter().map(cell).collect();
+            writeln!(model, "constraint alldifferent([{}]);", cells.join(", ")).unwrap();
+        }
+    }
+    for (f, v) in &kenken.givens {
+        writeln!(model, "constraint {} = {};", cell(f), v).unwrap();
+    }
+    writeln!(model).unwrap();
+    for area in &kenken.areas {
+        if kenken.hidden_ops {
+            writeln!(model, "constraint {};", hidden_constraint(area, &values)).unwrap();
+        } else {
+            writeln!(model, "constraint {};", constraint(area, &values)).unwrap();
+        }
+    }
+    writeln!(model, "\nsolve satisfy;\n").unwrap();
+    writeln!(
+        model,
+        "output [show(grid[r,c]) ++ if c == n then \"\\n\" else \" \" endif | r in 1..n, c in 1..n];"
+    )
+    .unwrap();
+    model
+}
+
+/// Parses the first solution printed by `minizinc` for a model of `to_minizinc`
+/// and checks it against the puzzle.
+pub fn parse_solution(kenken: &KenKen, output: &str) -> Result<Vec<Assignment>> {
+    if output.contains("=====UNSATISFIABLE=====") {
+        bail!("solver reports the puzzle as unsatisfiable");
+    }
+    if !output.lines().any(|line| line.trim() == SEPARATOR) {
+        bail!("no solution found in solver output");
+    }
+
+    let rows: Vec<&str> = output
+        .lines()
+        .map(str::trim)
+        .take_while(|line| *line != SEPARATOR)
+        .filter(|line| !line.is_empty() && !line.starts_with('%'))
+        .collect();
+    if rows.len() != kenken.size as usize {
+        bail!("expected {} rows, found {}", kenken.size, rows.len());
+    }
+
+    let mut grid = Assignment::empty();
+    for (y, row) in rows.iter().enumerate() {
+        let values: Vec<&str> = row.split_whitespace().collect();
+        if values.len() != kenken.size as usize {
+            bail!(
+                "row {} has {} values, expected {}",
+                y + 1,
+                values.len(),
+                kenken.size
+            );
+        }
+        for (x, value) in values.iter().enumerate() {
+            let value = value
+                .parse()
+                .map_err(|_| anyhow!("invalid value '{}' in row {}", value, y + 1))?;
+            grid.set(Field(x as u16, y as u16), value);
+        }
+    }
+
+    if let Err(errors) = kenken.validate_solution(&grid) {
+        let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        bail!(
+            "the solution does not solve the puzzle: {}",
+            errors.join("; ")
+        );
+    }
+    Ok(kenken
+        .areas
+        .iter()
+        .map(|area| grid.restrict(&area.fields))
+        .collect())
+}
+
+#[cfg(test)]
+mod test_mzn {
+    use super::*;
+    use crate::solve::{Backend, Outcome};
+
+    /// The grid as `minizinc` prints it for the output item of the model.
+    fn output(kenken: &KenKen, grid: &Assignment) -> String {
+        let mut output = String::new();
+        for y in 0..kenken.size {
+            let row: Vec<String> = (0..kenken.size)
+                .map(|x| grid.get(&Field(x, y)).unwrap().to_string())
+                .collect();
+            writeln!(output, "{}", row.join(" ")).unwrap();
+        }
+        writeln!(output, "{}", SEPARATOR).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // 1 2 3 / 2 3 1 / 3 1 2
+        let kenken = KenKen::new(
+            0,
+            3,
+            vec![
+                Area::new(Type::Add, 5, vec![Field(0, 0), Field(1, 0), Field(0, 1)]),
+                Area::new(Type::Sub, 2, vec![Field(2, 0), Field(2, 1)]),
+                Area::new(Type::Mul, 3, vec![Field(1, 1), Field(1, 2)]),
+                Area::new(Type::Concat, 32, vec![Field(0, 2), Field(2, 2)]),
+            ],
+        );
+        let model = to_minizinc(&kenken);
+        assert!(model.contains("sum([grid[1,1], grid[1,2], grid[2,1]]) = 5"));
+        assert!(model.contains("abs(grid[1,3] - grid[2,3]) = 2"));
+        assert!(model.contains("table([grid[3,1], grid[3,3]], [| 3, 2 |])"));
+
+        let (Outcome::Solved(sol), _) = Backend::Search.solver(None).solve(&kenken).unwrap() else {
+            panic!("no solution");
+        };
+        let mut grid = Assignment::empty();
+        for (f, v) in sol.iter().flat_map(|asg| asg.iter()) {
+            grid.set(f, v);
+        }
+        assert_eq!(
+            parse_solution(&kenken, &output(&kenken, &grid)).unwrap(),
+            sol
+        );
+
+        // a Latin square that misses the targets, with 1 and 2 swapped
+        let mut wrong = Assignment::empty();
+        for (f, v) in grid.iter() {
+            wrong.set(f, [2, 1, 3][v as usize - 1]);
+        }
+        assert!(parse_solution(&kenken, &output(&kenken, &wrong)).is_err());
+        assert!(parse_solution(&kenken, "=====UNSATISFIABLE=====").is_err());
+    }
+}
@@ -6,12 +6,50 @@ use anyhow::Result;
 use std::io::Write;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
-pub fn print(kenken: &KenKen, asgs: Vec<Assignment>, col_size: usize) -> Result<()> {
+/// Layout of the printed grid.
+pub struct PrintConfig {
+    pub col_size: usize,
+    /// Show the values 10 to 35 as single letters, from A for 10 (G for 16).
+    pub hex: bool,
+    /// Fields drawn on red, e.g. the areas of a conflict.
+    pub highlight: Vec<Field>,
+}
+
+impl PrintConfig {
+    /// Columns wide enough for the longest label, but compact for large grids.
+    pub fn for_kenken(kenken: &KenKen, hex: bool) -> Self {
+        let longest = kenken
+            .areas
+            .iter()
+            .map(|area| kenken.label(area, Type::symbol).len())
+            .max()
+            .unwrap_or(0);
+        let min = if kenken.size <= 9 { 10 } else { 4 };
+        PrintConfig {
+            col_size: (longest + 1).max(min),
+            hex,
+            highlight: vec![],
+        }
+    }
+}
+
+pub fn format_value(value: i64, hex: bool) -> String {
+    match value {
+        10..=35 if hex => char::from_digit(value as u32, 36)
+            .unwrap()
+            .to_ascii_uppercase()
+            .to_string(),
+        _ => value.to_string(),
+    }
+}
+
+pub fn print(kenken: &KenKen, asgs: Vec<Assignment>, config: &PrintConfig) -> Result<()> {
     let mut stdout = StandardStream::stdout(ColorChoice::Always);
+    let col_size = config.col_size;
 
     print_horizontal_separator(&mut stdout, kenken, 0, col_size)?;
     for i in 0..kenken.size {
-        print_row(&mut stdout, kenken, &asgs, i, col_size)?;
+        print_row(&mut stdout, kenken, &asgs, i, config)?;
         print_horizontal_separator(&mut stdout, kenken, i + 1, col_size)?;
     }
 
@@ -23,42 +61,71 @@ fn print_row<W>(
     kenken: &KenKen,
     asgs: &Vec<Assignment>,
     row: u16,
-    col_size: usize,
+    config: &PrintConfig,
 ) -> Result<()>
 where
     W: Write + WriteColor,
 {
     let size = kenken.size;
+    let col_size = config.col_size;
     print_vertical_sep(w, kenken, row, 0)?;
     for i in 1..=size {
-        if let Some(area) = kenken.is_id_field(Field(i - 1, row)) {
-            w.set_color(ColorSpec::new().set_bold(true))?;
-            match area.ty {
-                Type::Add => write!(w, "{}{:<width$}", "+", area.solution, width = col_size - 1)?,
-                Type::Mul => write!(w, "{}{:<width$}", "*", area.solution, width = col_size - 1)?,
-                Type::Div => write!(w, "{}{:<width$}", "/", area.solution, width = col_size - 1)?,
-                Type::Sub => write!(w, "{}{:<width$}", "-", area.solution, width = col_size - 1)?,
-                Type::Single => write!(w, "{:<width$}", area.solution, width = col_size)?,
-            }
-            w.reset()?;
+        let field = Field(i - 1, row);
+        let mut spec = highlight(config, &field);
+        if let Some(area) = kenken.is_id_field(field) {
+            w.set_color(spec.set_bold(true))?;
+            write!(
+                w,
+                "{:<width$}",
+                kenken.label(area, Type::symbol),
+                width = col_size
+            )?;
         } else {
+            w.set_color(&spec)?;
             write!(w, "{:width$}", " ", width = col_size)?;
         }
+        w.reset()?;
         print_vertical_sep(w, kenken, row, i)?;
     }
     writeln!(w, "")?;
 
     print_vertical_sep(w, kenken, row, 0)?;
     for i in 1..=size {
-        write!(w, "{:width$}", " ", width = col_size)?;
+        let field = Field(i - 1, row);
+        let marker = match (
+            kenken.on_main_diagonal(&field),
+            kenken.on_anti_diagonal(&field),
+        ) {
+            (true, true) => "X",
+            (true, false) => "\\",
+            (false, true) => "/",
+            (false, false) => " ",
+        };
+        w.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+        write!(w, "{:^width$}", marker, width = col_size)?;
+        w.reset()?;
         print_vertical_sep(w, kenken, row, i)?;
     }
     writeln!(w, "")?;
 
     print_vertical_sep(w, kenken, row, 0)?;
     for i in 1..=size {
-        if let Some(asg) = asgs.iter().find_map(|asg| asg.get(&Field(i - 1, row))) {
-            write!(w, "{:^width$}", asg, width = col_size)?;
+        if let Some(given) = kenken.given(&Field(i - 1, row)) {
+            w.set_color(ColorSpec::new().set_bold(true).set_fg(Some(Color::Cyan)))?;
+            write!(
+                w,
+                "{:^width$}",
+                format_value(given, config.hex),
+                width = col_size
+            )?;
+            w.reset()?;
+        } else if let Some(asg) = asgs.iter().find_map(|asg| asg.get(&Field(i - 1, row))) {
+            write!(
+                w,
+                "{:^width$}",
+                format_value(asg, config.hex),
+                width = col_size
+            )?;
         } else {
             write!(w, "{:width$}", " ", width = col_size)?;
         }
@@ -68,7 +135,9 @@ where
 
     print_vertical_sep(w, kenken, row, 0)?;
     for i in 1..=size {
+        w.set_color(&highlight(config, &Field(i - 1, row)))?;
         write!(w, "{:width$}", " ", width = col_size)?;
+        w.reset()?;
         print_vertical_sep(w, kenken, row, i)?;
     }
     writeln!(w, "")?;
@@ -76,6 +145,14 @@ where
     Ok(())
 }
 
+fn highlight(config: &PrintConfig, field: &Field) -> ColorSpec {
+    let mut spec = ColorSpec::new();
+    if config.highlight.contains(field) {
+        spec.set_bg(Some(Color::Red));
+    }
+    spec
+}
+
 fn print_vertical_sep<W>(w: &mut W, kenken: &KenKen, row: u16, after_col: u16) -> Result<()>
 where
     W: Write + WriteColor,
@@ -0,0 +1,518 @@
+use std::time::{Duration, Instant};
+
+use std::io::Write;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    asg::Assignment,
+    kenken::{Field, KenKen},
+    solve::{area_assignments, Deadline, Outcome, Solver, Stats},
+};
+
+/// A formula in conjunctive normal form, literals use the DIMACS convention
+/// (variables start at 1, negative literals are negated variables).
+#[derive(Debug, Clone, Default)]
+pub struct Cnf {
+    pub num_vars: usize,
+    pub clauses: Vec<Vec<i32>>,
+}
+
+impl Cnf {
+    fn new_var(&mut self) -> i32 {
+        self.num_vars += 1;
+        self.num_vars as i32
+    }
+
+    pub fn write_dimacs<W: Write>(&self, w: &mut W, comments: &[String]) -> Result<()> {
+        for comment in comments {
+            writeln!(w, "c {}", comment)?;
+        }
+        writeln!(w, "p cnf {} {}", self.num_vars, self.clauses.len())?;
+        for clause in &self.clauses {
+            for lit in clause {
+                write!(w, "{} ", lit)?;
+            }
+            writeln!(w, "0")?;
+        }
+        Ok(())
+    }
+
+    fn exactly_one(&mut self, lits: &[i32]) {
+        self.clauses.push(lits.to_vec());
+        for (i, a) in lits.iter().enumerate() {
+            for b in &lits[i + 1..] {
+                self.clauses.push(vec![-a, -b]);
+            }
+        }
+    }
+}
+
+/// One-hot encoding of a `KenKen`: a variable per cell and value, plus a
+/// selector variable per possible assignment of every area.
+pub struct Encoding {
+    pub cnf: Cnf,
+    pub size: u16,
+    /// The value set of the puzzle; cell variables refer to values by position.
+    pub values: Vec<i64>,
+    /// Per area: the selector variable of each possible assignment.
+    pub selectors: Vec<Vec<i32>>,
+}
+
+impl Encoding {
+    pub fn new(kenken: &KenKen) -> Self {
+        Self::restricted(
+            kenken,
+            &vec![true; kenken.areas.len()],
+            &vec![true; kenken.houses().len()],
+        )
+    }
+
+    /// The encoding with only the areas and houses (see `KenKen::houses`)
+    /// marked in `areas` and `houses`; every field still holds one value.
+    pub fn restricted(kenken: &KenKen, areas: &[bool], houses: &[bool]) -> Self {
+        let size = kenken.size;
+        let mut cnf = Cnf {
+            num_vars: (size as usize).pow(3),
+            clauses: vec![],
+        };
+        let values = kenken.values();
+        let index = |v: i64| values.iter().position(|&w| w == v).unwrap() as u16 + 1;
+        let var = |x: u16, y: u16, v: u16| cell_var(size, Field(x, y), v);
+
+        for x in 0..size {
+            for y in 0..size {
+                cnf.exactly_one(&(1..=size).map(|v| var(x, y, v)).collect::<Vec<_>>());
+            }
+        }
+        let all_houses = kenken.houses();
+        for v in 1..=size {
+            for (house, _) in all_houses.iter().zip(houses).filter(|(_, &on)| on) {
+                cnf.exactly_one(&house.iter().map(|f| var(f.0, f.1, v)).collect::<Vec<_>>());
+            }
+        }
+
+        let mut selectors = vec![];
+        for ((area, asgs), &on) in kenken.areas.iter().zip(area_assignments(kenken)).zip(areas) {
+            if !on {
+                selectors.push(vec![]);
+                continue;
+            }
+            let sels: Vec<i32> = asgs.iter().map(|_| cnf.new_var()).collect();
+            cnf.clauses.push(sels.clone());
+            for (sel, asg) in sels.iter().zip(&asgs) {
+                for (f, v) in asg.iter() {
+                    cnf.clauses.push(vec![-sel, cell_var(size, f, index(v))]);
+                }
+            }
+            for f in &area.fields {
+                for (i, &v) in values.iter().enumerate() {
+                    let mut support = vec![-cell_var(size, *f, i as u16 + 1)];
+                    support.extend(
+                        sels.iter()
+                            .zip(&asgs)
+                            .filter(|(_, asg)| asg.get(f) == Some(v))
+                            .map(|(sel, _)| *sel),
+                    );
+                    cnf.clauses.push(support);
+                }
+            }
+            selectors.push(sels);
+        }
+
+        Encoding {
+            cnf,
+            size,
+            values,
+            selectors,
+        }
+    }
+
+    pub fn comments(&self, kenken: &KenKen) -> Vec<String> {
+        let values: Vec<String> = self.values.iter().map(|v| v.to_string()).collect();
+        vec![
+            format!(
+                "KenKen {} of size {} with values {}",
+                kenken.id,
+                self.size,
+                values.join(",")
+            ),
+            format!(
+                "variable 1 + (y * {n} + x) * {n} + i - 1 means field (x,y) has the i-th value",
+                n = self.size
+            ),
+            format!(
+                "variables {}..={} select area assignments",
+                self.size as usize * self.size as usize * self.size as usize + 1,
+                self.cnf.num_vars
+            ),
+        ]
+    }
+
+    /// Whether the formula has a model, `Solved(())` if so.
+    pub fn satisfiable(&self, timeout: Option<Duration>) -> Outcome<()> {
+        let mut dpll = Dpll::new(&self.cnf);
+        let mut found = false;
+        if !dpll.run(&Deadline::after(timeout), |_| {
+            found = true;
+            false
+        }) {
+            Outcome::TimedOut
+        } else if found {
+            Outcome::Solved(())
+        } else {
+            Outcome::Infeasible
+        }
+    }
+
+    /// Reads a SAT solver's output for this encoding back into area assignments.
+    pub fn import(&self, kenken: &KenKen, output: &str) -> Result<Vec<Assignment>> {
+        let model = parse_model(output, self.cnf.num_vars)?;
+        if let Some(clause) = self.cnf.clauses.iter().find(|clause| {
+            !clause
+                .iter()
+                .any(|&l| model[l.unsigned_abs() as usize] == (l > 0))
+        }) {
+            bail!("model violates clause {:?}", clause);
+        }
+        Ok(self.decode(kenken, &model))
+    }
+
+    /// Reads the assignment of every area from a model, indexed by variable.
+    pub fn decode(&self, kenken: &KenKen, model: &[bool]) -> Vec<Assignment> {
+        kenken
+            .areas
+            .iter()
+            .map(|area| {
+                let mut asg = Assignment::empty();
+                for f in &area.fields {
+                    if let Some(i) =
+                        (1..=self.size).find(|&i| model[cell_var(self.size, *f, i) as usize])
+                    {
+                        asg.set(*f, self.values[i as usize - 1]);
+                    }
+                }
+                asg
+            })
+            .collect()
+    }
+}
+
+/// Parses the output of a SAT solver, either in the competition format
+/// (`s SATISFIABLE` and `v` lines) or as a plain list of literals.
+pub fn parse_model(input: &str, num_vars: usize) -> Result<Vec<bool>> {
+    let mut model = vec![false; num_vars + 1];
+    for line in input.lines() {
+        let line = line.trim();
+        if line.contains("UNSAT") {
+            bail!("solver reports the puzzle as unsatisfiable");
+        }
+        let lits = match line.strip_prefix('v') {
+            Some(rest) => rest,
+            None if line.starts_with(|c: char| c == '-' || c.is_ascii_digit()) => line,
+            None => continue,
+        };
+        for lit in lits.split_whitespace() {
+            let lit: i64 = lit
+                .parse()
+                .map_err(|_| anyhow!("invalid literal '{}'", lit))?;
+            if lit.unsigned_abs() as usize > num_vars {
+                bail!(
+                    "literal {} exceeds the {} variables of the encoding",
+                    lit,
+                    num_vars
+                );
+            }
+            if lit > 0 {
+                model[lit as usize] = true;
+            }
+        }
+    }
+    Ok(model)
+}
+
+/// The variable of `field` having the `index`-th value, counted from 1.
+pub fn cell_var(size: u16, field: Field, index: u16) -> i32 {
+    let size = size as i32;
+    1 + (field.1 as i32 * size + field.0 as i32) * size + index as i32 - 1
+}
+
+fn lit_index(lit: i32) -> usize {
+    lit.unsigned_abs() as usize * 2 + (lit < 0) as usize
+}
+
+fn lit_value(values: &[i8], lit: i32) -> i8 {
+    let v = values[lit.unsigned_abs() as usize];
+    if lit > 0 {
+        v
+    } else {
+        -v
+    }
+}
+
+/// A plain DPLL solver with two watched literals and chronological backtracking.
+struct Dpll {
+    clauses: Vec<Vec<i32>>,
+    positive: Vec<usize>,
+    watches: Vec<Vec<usize>>,
+    values: Vec<i8>,
+    trail: Vec<i32>,
+    /// Per decision: trail length before it, the decided literal and whether it was flipped.
+    levels: Vec<(usize, i32, bool)>,
+    head: usize,
+    nodes: u64,
+    empty: bool,
+}
+
+impl Dpll {
+    fn new(cnf: &Cnf) -> Self {
+        let mut dpll = Dpll {
+            clauses: cnf.clauses.clone(),
+            positive: vec![],
+            watches: vec![vec![]; 2 * cnf.num_vars + 2],
+            values: vec![0; cnf.num_vars + 1],
+            trail: vec![],
+            levels: vec![],
+            head: 0,
+            nodes: 0,
+            empty: false,
+        };
+        for (c, clause) in cnf.clauses.iter().enumerate() {
+            if clause.iter().all(|&l| l > 0) {
+                dpll.positive.push(c);
+            }
+            match clause.len() {
+                0 => dpll.empty = true,
+                1 => match lit_value(&dpll.values, clause[0]) {
+                    0 => dpll.assign(clause[0]),
+                    -1 => dpll.empty = true,
+                    _ => {}
+                },
+                _ => {
+                    dpll.watches[lit_index(clause[0])].push(c);
+                    dpll.watches[lit_index(clause[1])].push(c);
+                }
+            }
+        }
+        dpll
+    }
+
+    fn assign(&mut self, lit: i32) {
+        self.values[lit.unsigned_abs() as usize] = if lit > 0 { 1 } else { -1 };
+        self.trail.push(lit);
+    }
+
+    /// Unit propagation, returns `false` on a conflict.
+    fn propagate(&mut self) -> bool {
+        while self.head < self.trail.len() {
+            let falsified = -self.trail[self.head];
+            self.head += 1;
+            let watching = std::mem::take(&mut self.watches[lit_index(falsified)]);
+            let mut keep = Vec::with_capacity(watching.len());
+            let mut conflict = false;
+
+            for (k, &c) in watching.iter().enumerate() {
+                if conflict {
+                    keep.extend_from_slice(&watching[k..]);
+                    break;
+                }
+                let clause = &mut self.clauses[c];
+                if clause[0] == falsified {
+                    clause.swap(0, 1);
+                }
+                if lit_value(&self.values, clause[0]) == 1 {
+                    keep.push(c);
+                    continue;
+                }
+                if let Some(j) =
+                    (2..clause.len()).find(|&j| lit_value(&self.values, clause[j]) != -1)
+                {
+                    clause.swap(1, j);
+                    self.watches[lit_index(clause[1])].push(c);
+                    continue;
+                }
+                keep.push(c);
+                let unit = clause[0];
+                if lit_value(&self.values, unit) == -1 {
+                    conflict = true;
+                } else {
+                    self.assign(unit);
+                }
+            }
+
+            self.watches[lit_index(falsified)] = keep;
+            if conflict {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Branches on the smallest open all-positive clause, or sets a variable to false.
+    fn pick(&self) -> Option<i32> {
+        let mut best: Option<(usize, i32)> = None;
+        for &c in &self.positive {
+            let clause = &self.clauses[c];
+            if clause.iter().any(|&l| lit_value(&self.values, l) == 1) {
+                continue;
+            }
+            let open: Vec<i32> = clause
+                .iter()
+                .cloned()
+                .filter(|&l| lit_value(&self.values, l) == 0)
+                .collect();
+            if !open.is_empty() && best.is_none_or(|(n, _)| open.len() < n) {
+                best = Some((open.len(), open[0]));
+            }
+        }
+        best.map(|(_, lit)| lit).or_else(|| {
+            (1..self.values.len())
+                .find(|&v| self.values[v] == 0)
+                .map(|v| -(v as i32))
+        })
+    }
+
+    /// Undoes the last open decision and takes its other branch.
+    fn backtrack(&mut self) -> bool {
+        while let Some((pos, lit, flipped)) = self.levels.pop() {
+            for l in self.trail.drain(pos..) {
+                self.values[l.unsigned_abs() as usize] = 0;
+            }
+            self.head = pos;
+            if !flipped {
+                self.levels.push((pos, -lit, true));
+                self.assign(-lit);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Enumerates models until `on_model` returns `false`. Returns `false` on timeout.
+    fn run<F>(&mut self, deadline: &Deadline, mut on_model: F) -> bool
+    where
+        F: FnMut(&[bool]) -> bool,
+    {
+        if self.empty {
+            return true;
+        }
+        let mut ok = self.propagate();
+        loop {
+            while !ok {
+                if !self.backtrack() {
+                    return true;
+                }
+                ok = self.propagate();
+            }
+
+            self.nodes += 1;
+            if self.nodes.is_multiple_of(256) && deadline.expired() {
+                return false;
+            }
+
+            match self.pick() {
+                Some(lit) => {
+                    self.levels.push((self.trail.len(), lit, false));
+                    self.assign(lit);
+                    ok = self.propagate();
+                }
+                None => {
+                    let model: Vec<bool> = self.values.iter().map(|&v| v == 1).collect();
+                    if !on_model(&model) {
+                        return true;
+                    }
+                    ok = false;
+                }
+            }
+        }
+    }
+}
+
+pub struct SatSolver {
+    pub timeout: Option<Duration>,
+}
+
+impl SatSolver {
+    fn stats(&self, encoding: &Encoding, dpll: &Dpll, start: Instant) -> Stats {
+        Stats {
+            assignments: encoding.selectors.iter().map(|s| s.len()).sum(),
+            variables: encoding.cnf.num_vars,
+            constraints: encoding.cnf.clauses.len(),
+            nodes: dpll.nodes,
+            elapsed: start.elapsed(),
+        }
+    }
+}
+
+impl Solver for SatSolver {
+    fn name(&self) -> &'static str {
+        "sat"
+    }
+
+    fn solutions(
+        &self,
+        kenken: &KenKen,
+        limit: usize,
+    ) -> Result<(Outcome<Vec<Vec<Assignment>>>, Stats)> {
+        let start = Instant::now();
+        let encoding = Encoding::new(kenken);
+        let mut dpll = Dpll::new(&encoding.cnf);
+        let mut found = vec![];
+        let finished = dpll.run(&Deadline::after(self.timeout), |model| {
+            found.push(encoding.decode(kenken, model));
+            found.len() < limit
+        });
+
+        let outcome = if finished {
+            Outcome::found(found)
+        } else {
+            Outcome::TimedOut
+        };
+        Ok((outcome, self.stats(&encoding, &dpll, start)))
+    }
+}
+
+#[cfg(test)]
+mod test_dimacs {
+    use super::*;
+
+    #[test]
+    fn test_import_model() {
+        let kenken: KenKen = ron::from_str(include_str!("../old/kenken43208.ron")).unwrap();
+        let encoding = Encoding::new(&kenken);
+        let mut dpll = Dpll::new(&encoding.cnf);
+        let mut found = vec![];
+        dpll.run(&Deadline::after(None), |model| {
+            found = model.to_vec();
+            false
+        });
+
+        let mut output = String::from("s SATISFIABLE\nv");
+        for (var, &value) in found.iter().enumerate().skip(1) {
+            let lit = if value { var as i32 } else { -(var as i32) };
+            output.push_str(&format!(" {}", lit));
+        }
+        output.push_str(" 0\n");
+
+        let imported = encoding.import(&kenken, &output).unwrap();
+        assert_eq!(imported, encoding.decode(&kenken, &found));
+
+        let mut dimacs = vec![];
+        encoding.cnf.write_dimacs(&mut dimacs, &[]).unwrap();
+        let header = String::from_utf8(dimacs)
+            .unwrap()
+            .lines()
+            .next()
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            header,
+            format!(
+                "p cnf {} {}",
+                encoding.cnf.num_vars,
+                encoding.cnf.clauses.len()
+            )
+        );
+        assert!(encoding.import(&kenken, "s UNSATISFIABLE").is_err());
+    }
+}
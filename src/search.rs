@@ -0,0 +1,358 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    asg::Assignment,
+    kenken::{Field, KenKen},
+    solve::{area_assignments, Deadline, Outcome, Solver, Stats},
+};
+
+/// Backtracking over cage assignments, always branching on the area with the
+/// fewest assignments that are still consistent with the partial grid.
+pub struct SearchSolver {
+    pub timeout: Option<Duration>,
+}
+
+struct Search {
+    /// Per area: the candidate assignments as `(cell, value index)` pairs.
+    options: Vec<Vec<Vec<(usize, usize)>>>,
+    /// Per cell: the houses (rows, columns, diagonals) it belongs to.
+    houses: Vec<Vec<usize>>,
+    /// Per house: its cells.
+    house_cells: Vec<Vec<usize>>,
+    /// Per cell: the area containing it.
+    area_of: Vec<usize>,
+    /// Per house: the values placed in it, one bit per value index.
+    used: Vec<u64>,
+    chosen: Vec<Option<usize>>,
+    limit: usize,
+    found: Vec<Vec<usize>>,
+    deadline: Deadline,
+    timed_out: bool,
+    nodes: u64,
+}
+
+impl Search {
+    /// Fails for more values than the bits of a house's `used` mask.
+    fn new(
+        kenken: &KenKen,
+        asgs: &[Vec<Assignment>],
+        limit: usize,
+        deadline: Deadline,
+    ) -> Result<Self> {
+        let size = kenken.size as usize;
+        let values = kenken.values();
+        if values.len() > u64::BITS as usize {
+            bail!(
+                "the search supports at most {} values, not {}",
+                u64::BITS,
+                values.len()
+            );
+        }
+        let index = |v: i64| values.iter().position(|&w| w == v).unwrap();
+        let cell = |f: &Field| f.1 as usize * size + f.0 as usize;
+        let options: Vec<Vec<Vec<(usize, usize)>>> = asgs
+            .iter()
+            .map(|asgs| {
+                asgs.iter()
+                    .map(|asg| asg.iter().map(|(f, v)| (cell(&f), index(v))).collect())
+                    .collect()
+            })
+            .collect();
+
+        let house_cells: Vec<Vec<usize>> = kenken
+            .houses()
+            .iter()
+            .map(|house| house.iter().map(cell).collect())
+            .collect();
+        let mut houses = vec![vec![]; size * size];
+        for (h, cells) in house_cells.iter().enumerate() {
+            for &c in cells {
+                houses[c].push(h);
+            }
+        }
+        let mut area_of = vec![0; size * size];
+        for (a, area) in kenken.areas.iter().enumerate() {
+            for f in &area.fields {
+                area_of[cell(f)] = a;
+            }
+        }
+
+        Ok(Search {
+            chosen: vec![None; options.len()],
+            options,
+            houses,
+            used: vec![0; house_cells.len()],
+            house_cells,
+            area_of,
+            limit,
+            found: vec![],
+            deadline,
+            timed_out: false,
+            nodes: 0,
+        })
+    }
+
+    fn fits(&self, option: &[(usize, usize)]) -> bool {
+        option.iter().all(|&(cell, v)| {
+            self.houses[cell]
+                .iter()
+                .all(|&h| self.used[h] & (1 << v) == 0)
+        })
+    }
+
+    fn toggle(&mut self, option: &[(usize, usize)]) {
+        for &(cell, v) in option {
+            for &h in &self.houses[cell] {
+                self.used[h] ^= 1 << v;
+            }
+        }
+    }
+
+    /// Narrows the fitting options until every value missing from a house can
+    /// still be placed in it: if only one open area can place the value in the
+    /// house, that area must. Returns `false` on a dead end.
+    fn propagate(&self, fitting: &mut [Vec<usize>]) -> bool {
+        let values = self.house_cells[0].len();
+        loop {
+            let mut domains = vec![0u64; self.area_of.len()];
+            for (area, options) in fitting.iter().enumerate() {
+                for &o in options {
+                    for &(cell, v) in &self.options[area][o] {
+                        domains[cell] |= 1 << v;
+                    }
+                }
+            }
+
+            let mut changed = false;
+            for (h, cells) in self.house_cells.iter().enumerate() {
+                for v in 0..values {
+                    if self.used[h] & (1 << v) != 0 {
+                        continue;
+                    }
+                    let mut owners = cells
+                        .iter()
+                        .filter(|&&c| domains[c] & (1 << v) != 0)
+                        .map(|&c| self.area_of[c]);
+                    let owner = match owners.next() {
+                        Some(owner) => owner,
+                        None => return false,
+                    };
+                    if owners.any(|a| a != owner) {
+                        continue;
+                    }
+                    let before = fitting[owner].len();
+                    fitting[owner].retain(|&o| {
+                        self.options[owner][o]
+                            .iter()
+                            .any(|&(cell, w)| w == v && self.houses[cell].contains(&h))
+                    });
+                    if fitting[owner].is_empty() {
+                        return false;
+                    }
+                    changed |= fitting[owner].len() < before;
+                }
+            }
+            if !changed {
+                return true;
+            }
+        }
+    }
+
+    /// Branches on the area with the fewest fitting options. The options of each
+    /// open area that still fit are passed down, so deeper levels only filter
+    /// what their parent kept.
+    fn run(&mut self, candidates: &[Vec<usize>]) {
+        if self.found.len() >= self.limit || self.timed_out {
+            return;
+        }
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(1024) && self.deadline.expired() {
+            self.timed_out = true;
+            return;
+        }
+
+        let mut fitting = vec![vec![]; self.options.len()];
+        let mut best: Option<usize> = None;
+        for area in 0..self.options.len() {
+            if self.chosen[area].is_some() {
+                continue;
+            }
+            fitting[area] = candidates[area]
+                .iter()
+                .cloned()
+                .filter(|&o| self.fits(&self.options[area][o]))
+                .collect();
+            if fitting[area].is_empty() {
+                return;
+            }
+        }
+        if !self.propagate(&mut fitting) {
+            return;
+        }
+        for area in 0..self.options.len() {
+            if self.chosen[area].is_none()
+                && best.is_none_or(|b| fitting[area].len() < fitting[b].len())
+            {
+                best = Some(area);
+            }
+        }
+
+        let area = match best {
+            Some(area) => area,
+            None => {
+                self.found
+                    .push(self.chosen.iter().map(|c| c.unwrap()).collect());
+                return;
+            }
+        };
+
+        for o in std::mem::take(&mut fitting[area]) {
+            let option = self.options[area][o].clone();
+            self.toggle(&option);
+            self.chosen[area] = Some(o);
+            self.run(&fitting);
+            self.chosen[area] = None;
+            self.toggle(&option);
+            if self.found.len() >= self.limit || self.timed_out {
+                return;
+            }
+        }
+    }
+
+    fn stats(&self, start: Instant) -> Stats {
+        let assignments = self.options.iter().map(|o| o.len()).sum();
+        Stats {
+            assignments,
+            variables: assignments,
+            constraints: self.used.len(),
+            nodes: self.nodes,
+            elapsed: start.elapsed(),
+        }
+    }
+}
+
+impl SearchSolver {
+    /// Finds up to `limit` solutions which only use the given assignments of
+    /// every area, e.g. a subset of `area_assignments`.
+    pub fn solutions_among(
+        &self,
+        kenken: &KenKen,
+        all: &[Vec<Assignment>],
+        limit: usize,
+    ) -> Result<(Outcome<Vec<Vec<Assignment>>>, Stats)> {
+        let start = Instant::now();
+        let mut search = Search::new(kenken, all, limit, Deadline::after(self.timeout))?;
+        let candidates: Vec<Vec<usize>> =
+            all.iter().map(|asgs| (0..asgs.len()).collect()).collect();
+        search.run(&candidates);
+        let stats = search.stats(start);
+
+        if search.timed_out {
+            return Ok((Outcome::TimedOut, stats));
+        }
+        let found = search
+            .found
+            .into_iter()
+            .map(|chosen| {
+                chosen
+                    .into_iter()
+                    .enumerate()
+                    .map(|(area, o)| all[area][o].clone())
+                    .collect()
+            })
+            .collect();
+        Ok((Outcome::found(found), stats))
+    }
+}
+
+impl Solver for SearchSolver {
+    fn name(&self) -> &'static str {
+        "search"
+    }
+
+    fn solutions(
+        &self,
+        kenken: &KenKen,
+        limit: usize,
+    ) -> Result<(Outcome<Vec<Vec<Assignment>>>, Stats)> {
+        self.solutions_among(kenken, &area_assignments(kenken), limit)
+    }
+}
+
+#[cfg(test)]
+mod test_search {
+    use super::*;
+    use crate::kenken::{Area, Type};
+
+    /// A 2x2 puzzle of a column area (0,0), (0,1) and single fields (1,0), (1,1).
+    fn puzzle() -> KenKen {
+        KenKen::new(
+            0,
+            2,
+            vec![
+                Area::new(Type::Add, 3, vec![Field(0, 0), Field(0, 1)]),
+                Area::new(Type::Single, 1, vec![Field(1, 0)]),
+                Area::new(Type::Single, 2, vec![Field(1, 1)]),
+            ],
+        )
+    }
+
+    fn asg(values: &[(Field, i64)]) -> Assignment {
+        let mut asg = Assignment::empty();
+        for &(f, v) in values {
+            asg.set(f, v);
+        }
+        asg
+    }
+
+    #[test]
+    fn test_forced_owner() {
+        let kenken = puzzle();
+        let asgs = vec![
+            vec![
+                asg(&[(Field(0, 0), 1), (Field(0, 1), 2)]),
+                asg(&[(Field(0, 0), 2), (Field(0, 1), 1)]),
+            ],
+            vec![asg(&[(Field(1, 0), 1)])],
+            vec![asg(&[(Field(1, 1), 1)]), asg(&[(Field(1, 1), 2)])],
+        ];
+        let search = Search::new(&kenken, &asgs, 1, Deadline::after(None)).unwrap();
+        let mut fitting = vec![vec![0, 1], vec![0], vec![0, 1]];
+        assert!(search.propagate(&mut fitting));
+        // only the column area can place 2 in the first row
+        assert_eq!(fitting[0], vec![1]);
+        // and then only (1,1) can place 2 in the second row
+        assert_eq!(fitting[2], vec![1]);
+    }
+
+    #[test]
+    fn test_dead_end() {
+        let kenken = puzzle();
+        // nothing places 2 in the first row
+        let asgs = vec![
+            vec![asg(&[(Field(0, 0), 1), (Field(0, 1), 2)])],
+            vec![asg(&[(Field(1, 0), 1)])],
+            vec![asg(&[(Field(1, 1), 1)]), asg(&[(Field(1, 1), 2)])],
+        ];
+        let search = Search::new(&kenken, &asgs, 1, Deadline::after(None)).unwrap();
+        assert!(!search.propagate(&mut [vec![0], vec![0], vec![0, 1]]));
+        let (outcome, _) = SearchSolver { timeout: None }
+            .solutions_among(&kenken, &asgs, 1)
+            .unwrap();
+        assert_eq!(outcome, Outcome::Infeasible);
+    }
+
+    #[test]
+    fn test_too_many_values() {
+        let kenken = KenKen {
+            areas: vec![],
+            size: 65,
+            ..puzzle()
+        };
+        assert!(SearchSolver { timeout: None }
+            .solutions(&kenken, 1)
+            .is_err());
+    }
+}
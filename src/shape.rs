@@ -0,0 +1,325 @@
+use rand::{
+    distributions::WeightedIndex,
+    prelude::{Distribution, SliceRandom},
+    Rng,
+};
+
+use crate::kenken::{connected, Field, Symmetry};
+
+/// Constraints on the layout of the areas of a generated puzzle.
+#[derive(Debug, Clone)]
+pub struct ShapeConfig {
+    pub min_size: usize,
+    pub max_size: usize,
+    /// Relative frequencies of the area sizes, indexed by size. Sizes beyond the
+    /// end are not drawn; an empty list draws all sizes from `min_size` to
+    /// `max_size` alike.
+    pub size_weights: Vec<f32>,
+    pub max_singletons: Option<usize>,
+    /// Allow areas of three or more fields in a single row or column.
+    pub straight: bool,
+    /// Allow areas of three or more fields forming an L: a row and a column
+    /// segment meeting at their ends.
+    pub l_shapes: bool,
+    /// Allow areas containing a 2x2 block.
+    pub squares: bool,
+}
+
+impl ShapeConfig {
+    pub fn new(min_size: usize, max_size: usize) -> Self {
+        ShapeConfig {
+            min_size,
+            max_size,
+            size_weights: vec![],
+            max_singletons: None,
+            straight: true,
+            l_shapes: true,
+            squares: true,
+        }
+    }
+
+    /// Parses a size histogram given as `size:weight` pairs, e.g. `2:3,3:2,4:1`.
+    pub fn parse_weights(s: &str) -> Result<Vec<f32>, String> {
+        let mut weights = vec![];
+        for part in s.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            let (size, weight) = part
+                .split_once(':')
+                .ok_or_else(|| format!("expected size:weight, found '{}'", part))?;
+            let size: usize = size
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid size '{}'", size))?;
+            let weight: f32 = weight
+                .trim()
+                .parse()
+                .ok()
+                .filter(|w: &f32| *w >= 0.0)
+                .ok_or_else(|| format!("invalid weight '{}'", weight))?;
+            if size == 0 {
+                return Err("areas have at least one field".to_string());
+            }
+            if weights.len() <= size {
+                weights.resize(size + 1, 0.0);
+            }
+            weights[size] = weight;
+        }
+        Ok(weights)
+    }
+
+    /// The weight of each size from 0 to `max_size`, zero outside `min_size..=max_size`.
+    fn weights(&self) -> Vec<f32> {
+        (0..=self.max_size)
+            .map(|size| match size {
+                _ if size < self.min_size.max(1) => 0.0,
+                _ if self.size_weights.is_empty() => 1.0,
+                _ => self.size_weights.get(size).cloned().unwrap_or(0.0),
+            })
+            .collect()
+    }
+
+    /// Whether any area size can be drawn at all.
+    pub fn check(&self) -> Result<(), String> {
+        if self.min_size > self.max_size {
+            return Err(format!(
+                "minimum area size {} exceeds maximum {}",
+                self.min_size, self.max_size
+            ));
+        }
+        if self.weights().iter().all(|&w| w == 0.0) {
+            return Err(format!(
+                "no area size between {} and {} has a weight",
+                self.min_size, self.max_size
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn allows(&self, fields: &[Field]) -> bool {
+        (self.straight || !is_straight(fields))
+            && (self.l_shapes || !is_l_shape(fields))
+            && (self.squares || !has_square(fields))
+    }
+}
+
+pub fn is_straight(fields: &[Field]) -> bool {
+    fields.len() >= 3
+        && (fields.iter().all(|f| f.0 == fields[0].0) || fields.iter().all(|f| f.1 == fields[0].1))
+}
+
+pub fn is_l_shape(fields: &[Field]) -> bool {
+    fields.len() >= 3
+        && !is_straight(fields)
+        && fields.iter().any(|corner| {
+            let row: Vec<&Field> = fields.iter().filter(|f| f.1 == corner.1).collect();
+            let col: Vec<&Field> = fields.iter().filter(|f| f.0 == corner.0).collect();
+            row.len() + col.len() == fields.len() + 1
+                && (row.iter().all(|f| f.0 >= corner.0) || row.iter().all(|f| f.0 <= corner.0))
+                && (col.iter().all(|f| f.1 >= corner.1) || col.iter().all(|f| f.1 <= corner.1))
+        })
+}
+
+pub fn has_square(fields: &[Field]) -> bool {
+    fields.iter().any(|f| {
+        [
+            Field(f.0 + 1, f.1),
+            Field(f.0, f.1 + 1),
+            Field(f.0 + 1, f.1 + 1),
+        ]
+        .iter()
+        .all(|g| fields.contains(g))
+    })
+}
+
+/// A random partition of the grid into connected areas satisfying `config`
+/// and invariant under `symmetry`, or `None` if none was found in `attempts`
+/// tries. Areas are grown one after the other from the first free field in
+/// reading order, each to a size drawn from the histogram, by adding random
+/// free neighbors, and placed together with their images.
+pub fn random_partition<R: Rng>(
+    size: u16,
+    config: &ShapeConfig,
+    symmetry: Symmetry,
+    attempts: usize,
+    rng: &mut R,
+) -> Option<Vec<Vec<Field>>> {
+    let dist = WeightedIndex::new(config.weights()).ok()?;
+    (0..attempts).find_map(|_| try_partition(size, config, symmetry, &dist, rng))
+}
+
+fn try_partition<R: Rng>(
+    size: u16,
+    config: &ShapeConfig,
+    symmetry: Symmetry,
+    dist: &WeightedIndex<f32>,
+    rng: &mut R,
+) -> Option<Vec<Vec<Field>>> {
+    // stays symmetric, as areas are placed with all their images
+    let mut free = vec![vec![true; size as usize]; size as usize];
+    let mut areas: Vec<Vec<Field>> = vec![];
+    for y in 0..size {
+        for x in 0..size {
+            if !free[x as usize][y as usize] {
+                continue;
+            }
+            let fields = (0..20).find_map(|_| {
+                let fields = grow(Field(x, y), dist.sample(rng), &free, symmetry, rng);
+                (fields.len() >= config.min_size && config.allows(&fields)).then_some(fields)
+            })?;
+            for k in 0..symmetry.order() {
+                let mut image: Vec<Field> =
+                    fields.iter().map(|f| symmetry.image(k, f, size)).collect();
+                image.sort_by_key(|f| (f.1, f.0));
+                if areas.contains(&image) {
+                    continue;
+                }
+                for f in &image {
+                    free[f.0 as usize][f.1 as usize] = false;
+                }
+                areas.push(image);
+            }
+        }
+    }
+
+    let singletons = areas.iter().filter(|area| area.len() == 1).count();
+    if config.max_singletons.is_some_and(|max| singletons > max) {
+        return None;
+    }
+    Some(areas)
+}
+
+/// Whether every image of the fields is either the same or disjoint, so that
+/// the images can be areas side by side.
+fn separable(fields: &[Field], symmetry: Symmetry, size: u16) -> bool {
+    let mut sorted = fields.to_vec();
+    sorted.sort();
+    (1..symmetry.order()).all(|k| {
+        let mut image: Vec<Field> = fields.iter().map(|f| symmetry.image(k, f, size)).collect();
+        image.sort();
+        image == sorted || image.iter().all(|f| !fields.contains(f))
+    })
+}
+
+/// A random connected set of up to `target` free fields containing `start`,
+/// which is separable under `symmetry`. A field whose addition would overlap
+/// an image is added with all images of the area, if they are connected.
+fn grow<R: Rng>(
+    start: Field,
+    target: usize,
+    free: &[Vec<bool>],
+    symmetry: Symmetry,
+    rng: &mut R,
+) -> Vec<Field> {
+    let size = free.len() as u16;
+    let mut fields = vec![start];
+    while fields.len() < target {
+        let mut candidates: Vec<Field> = fields
+            .iter()
+            .flat_map(|f| {
+                [
+                    (f.0.checked_sub(1), Some(f.1)),
+                    (Some(f.0 + 1), Some(f.1)),
+                    (Some(f.0), f.1.checked_sub(1)),
+                    (Some(f.0), Some(f.1 + 1)),
+                ]
+            })
+            .filter_map(|(x, y)| Some(Field(x?, y?)))
+            .filter(|n| {
+                n.0 < size && n.1 < size && free[n.0 as usize][n.1 as usize] && !fields.contains(n)
+            })
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+        candidates.shuffle(rng);
+
+        let next = candidates.into_iter().find_map(|n| {
+            let mut grown = fields.clone();
+            grown.push(n);
+            if separable(&grown, symmetry, size) {
+                return Some(grown);
+            }
+            let mut closure: Vec<Field> =
+                grown.iter().flat_map(|f| symmetry.orbit(f, size)).collect();
+            closure.sort();
+            closure.dedup();
+            (closure.len() <= target && connected(&closure)).then_some(closure)
+        });
+        match next {
+            Some(next) => fields = next,
+            None => break,
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod test_shape {
+    use super::*;
+    use crate::kenken::{Area, KenKen, Type};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_shapes() {
+        let line = [Field(0, 0), Field(1, 0), Field(2, 0)];
+        let l = [Field(0, 0), Field(0, 1), Field(1, 1), Field(2, 1)];
+        let t = [Field(0, 0), Field(1, 0), Field(2, 0), Field(1, 1)];
+        let square = [Field(0, 0), Field(1, 0), Field(0, 1), Field(1, 1)];
+        assert!(is_straight(&line) && !is_l_shape(&line) && !has_square(&line));
+        assert!(!is_straight(&l) && is_l_shape(&l) && !has_square(&l));
+        assert!(!is_straight(&t) && !is_l_shape(&t) && !has_square(&t));
+        assert!(!is_straight(&square) && !is_l_shape(&square) && has_square(&square));
+    }
+
+    #[test]
+    fn test_partition() {
+        let mut config = ShapeConfig::new(1, 4);
+        config.size_weights = ShapeConfig::parse_weights("1:1, 2:2, 3:2, 4:1").unwrap();
+        config.max_singletons = Some(2);
+        config.straight = false;
+        config.squares = false;
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..20 {
+            let areas = random_partition(6, &config, Symmetry::None, 1000, &mut rng).unwrap();
+            let mut fields: Vec<Field> = areas.iter().flatten().cloned().collect();
+            fields.sort();
+            fields.dedup();
+            assert_eq!(fields.len(), 36);
+            assert!(areas.iter().filter(|area| area.len() == 1).count() <= 2);
+            for area in &areas {
+                assert!(area.len() <= 4 && connected(area) && config.allows(area));
+            }
+        }
+    }
+
+    #[test]
+    fn test_symmetric_partition() {
+        let config = ShapeConfig::new(1, 4);
+        let mut rng = StdRng::seed_from_u64(2);
+        for symmetry in [Symmetry::Rot180, Symmetry::Rot90, Symmetry::Mirror] {
+            for size in [5, 6] {
+                let areas = random_partition(size, &config, symmetry, 1000, &mut rng)
+                    .unwrap()
+                    .into_iter()
+                    .map(|fields| Area::new(Type::Single, 0, fields))
+                    .collect();
+                let kenken = KenKen {
+                    symmetry,
+                    ..KenKen::new(0, size, areas)
+                };
+                assert_eq!(
+                    kenken.areas.iter().map(|a| a.fields.len()).sum::<usize>(),
+                    (size * size) as usize
+                );
+                assert!(kenken
+                    .areas
+                    .iter()
+                    .all(|a| a.fields.len() <= 4 && connected(&a.fields)));
+                assert!(
+                    kenken.symmetric_layout(),
+                    "{:?} {:?}",
+                    symmetry,
+                    kenken.areas
+                );
+            }
+        }
+    }
+}
@@ -1,57 +1,542 @@
+use std::{
+    io::Write,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use clap::ArgEnum;
 use good_lp::{
     default_solver, variable, variables, Constraint, Expression, Solution, SolverModel, Variable,
 };
 
-use crate::{asg::Assignment, KenKen};
+use crate::{asg::Assignment, sat::SatSolver, search::SearchSolver, KenKen};
+
+/// A backend that can solve a `KenKen` and count its solutions.
+///
+/// Errors are reserved for failures of the backend itself; unsolvable puzzles
+/// and exceeded timeouts are reported through `Outcome`.
+pub trait Solver {
+    fn name(&self) -> &'static str;
+
+    /// Finds up to `limit` solutions, each given as one assignment per area.
+    fn solutions(
+        &self,
+        kenken: &KenKen,
+        limit: usize,
+    ) -> Result<(Outcome<Vec<Vec<Assignment>>>, Stats)>;
+
+    /// Returns one assignment per area which together fill the grid.
+    fn solve(&self, kenken: &KenKen) -> Result<(Outcome<Vec<Assignment>>, Stats)> {
+        let (outcome, stats) = self.solutions(kenken, 1)?;
+        Ok((outcome.map(|mut found| found.pop().unwrap()), stats))
+    }
+
+    /// Counts the solutions of `kenken`, stopping as soon as `limit` are found.
+    fn count(&self, kenken: &KenKen, limit: usize) -> Result<(Outcome<usize>, Stats)> {
+        let (outcome, stats) = self.solutions(kenken, limit)?;
+        Ok((outcome.map(|found| found.len()), stats))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome<T> {
+    Solved(T),
+    Infeasible,
+    TimedOut,
+}
+
+impl<T> Outcome<T> {
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Outcome<U> {
+        match self {
+            Outcome::Solved(t) => Outcome::Solved(f(t)),
+            Outcome::Infeasible => Outcome::Infeasible,
+            Outcome::TimedOut => Outcome::TimedOut,
+        }
+    }
+}
 
-pub fn solve(kenken: &KenKen) -> Vec<Assignment> {
-    let mut asgs = Vec::new();
-    let objective: Expression = 0.into();
-    let mut constraints = Vec::<Constraint>::new();
-    let mut vars = variables!();
-    let mut indexed_vars = Vec::<Variable>::new();
+impl<T> Outcome<Vec<T>> {
+    /// Finishing without any solution means the puzzle is infeasible.
+    pub fn found(found: Vec<T>) -> Self {
+        if found.is_empty() {
+            Outcome::Infeasible
+        } else {
+            Outcome::Solved(found)
+        }
+    }
+}
 
-    let mut idx = 0;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum Backend {
+    Mip,
+    Search,
+    Sat,
+}
 
-    for area in &kenken.areas {
-        let area_asgs = area.possible_assignments(kenken.size);
-        let mut area_vars = Vec::<Variable>::new();
-        for asg in area_asgs {
-            let var = vars.add(variable().binary().name(format!("x_{}", idx)));
-            area_vars.push(var);
-            asgs.push(asg);
-            idx += 1;
+impl Backend {
+    pub fn solver(self, timeout: Option<Duration>) -> Box<dyn Solver> {
+        match self {
+            Backend::Mip => Box::new(MipSolver { timeout }),
+            Backend::Search => Box::new(SearchSolver { timeout }),
+            Backend::Sat => Box::new(SatSolver { timeout }),
         }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Stats {
+    /// Number of cage assignments the backend had to consider.
+    pub assignments: usize,
+    pub variables: usize,
+    pub constraints: usize,
+    /// Search nodes (MIP: number of solver calls).
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "assignments: {}, variables: {}, constraints: {}, nodes: {}, time: {:.3}s",
+            self.assignments,
+            self.variables,
+            self.constraints,
+            self.nodes,
+            self.elapsed.as_secs_f64()
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline(Option<Instant>);
+
+impl Deadline {
+    pub fn after(timeout: Option<Duration>) -> Self {
+        Deadline(timeout.map(|t| Instant::now() + t))
+    }
+
+    pub fn expired(&self) -> bool {
+        self.0.is_some_and(|d| Instant::now() >= d)
+    }
+
+    pub fn remaining(&self) -> Option<Duration> {
+        self.0.map(|d| d.saturating_duration_since(Instant::now()))
+    }
+}
+
+/// The possible assignments of every area, in the order of `kenken.areas`.
+pub fn area_assignments(kenken: &KenKen) -> Vec<Vec<Assignment>> {
+    kenken
+        .areas
+        .iter()
+        .map(|area| kenken.possible_assignments(area))
+        .collect()
+}
+
+/// Writes the MIP model of `kenken` as an MPS file if `path` ends in `.mps`,
+/// as an LP file otherwise.
+pub fn write_model(kenken: &KenKen, path: &Path) -> Result<()> {
+    let model = MipModel::new(kenken);
+    let mut header = vec![format!(
+        "KenKen {}: variable a<area>_<values> selects the values of the area's fields",
+        kenken.id
+    )];
+    for (a, area) in kenken.areas.iter().enumerate() {
+        let fields: Vec<String> = area
+            .fields
+            .iter()
+            .map(|f| format!("({},{})", f.0, f.1))
+            .collect();
+        header.push(format!(
+            "area{}: {:?} {} {}",
+            a,
+            area.ty,
+            area.solution,
+            fields.join(" ")
+        ));
+    }
 
-        constraints.push(area_vars.iter().sum::<Expression>().eq(1i32));
-        indexed_vars.append(&mut area_vars)
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    if path.extension().is_some_and(|ext| ext == "mps") {
+        model.write_mps(&mut file, &header)
+    } else {
+        model.write_lp(&mut file, &header)
     }
+}
+
+pub struct MipSolver {
+    pub timeout: Option<Duration>,
+}
+
+/// A variable of the LP format for rows without any other, which cannot
+/// clash with the `a<area>_<values>` names.
+const DUMMY: &str = "a_dummy";
+
+struct MipModel {
+    asgs: Vec<Assignment>,
+    /// Per variable: `a<area>_<value>_<value>...`, the values in the order of
+    /// `Area.fields`, with `m` in place of a minus sign.
+    names: Vec<String>,
+    area_vars: Vec<Vec<usize>>,
+    conflicts: Vec<(usize, usize)>,
+}
+
+impl MipModel {
+    fn new(kenken: &KenKen) -> Self {
+        let mut asgs = Vec::new();
+        let mut names = Vec::new();
+        let mut area_vars = Vec::new();
 
-    println!("Total number of assignments: {}", asgs.len());
+        for (a, (area, area_asgs)) in kenken
+            .areas
+            .iter()
+            .zip(area_assignments(kenken))
+            .enumerate()
+        {
+            area_vars.push((asgs.len()..asgs.len() + area_asgs.len()).collect());
+            for asg in &area_asgs {
+                let mut name = format!("a{}", a);
+                for f in &area.fields {
+                    name.push_str(&format!("_{}", asg.get(f).unwrap()).replace('-', "m"));
+                }
+                names.push(name);
+            }
+            asgs.extend(area_asgs);
+        }
 
-    for (i, asgi) in asgs.iter().enumerate() {
-        for (j, asgj) in asgs.iter().enumerate() {
-            if i != j && asgi.conflict_asg(asgj) {
-                let vi = indexed_vars[i];
-                let vj = indexed_vars[j];
-                constraints.push((vi + vj).leq(1i32));
+        let mut conflicts = Vec::new();
+        for (i, asgi) in asgs.iter().enumerate() {
+            for (j, asgj) in asgs.iter().enumerate().skip(i + 1) {
+                if kenken.conflict(asgi, asgj) {
+                    conflicts.push((i, j));
+                }
             }
         }
+
+        MipModel {
+            asgs,
+            names,
+            area_vars,
+            conflicts,
+        }
     }
 
-    let mut model = vars.minimise(objective).using(default_solver);
-    for constr in constraints {
-        model.add_constraint(constr);
+    /// Solves the model, excluding the previously found solutions in `cuts`.
+    fn solve(&self, cuts: &[Vec<usize>], deadline: &Deadline) -> Result<Outcome<Vec<usize>>> {
+        let objective: Expression = 0.into();
+        let mut constraints = Vec::<Constraint>::new();
+        let mut vars = variables!();
+        let indexed_vars: Vec<Variable> = (0..self.asgs.len())
+            .map(|idx| vars.add(variable().binary().name(self.names[idx].clone())))
+            .collect();
+
+        for area in &self.area_vars {
+            constraints.push(
+                area.iter()
+                    .map(|&i| indexed_vars[i])
+                    .sum::<Expression>()
+                    .eq(1i32),
+            );
+        }
+
+        for &(i, j) in &self.conflicts {
+            constraints.push((indexed_vars[i] + indexed_vars[j]).leq(1i32));
+        }
+
+        for cut in cuts {
+            constraints.push(
+                cut.iter()
+                    .map(|&i| indexed_vars[i])
+                    .sum::<Expression>()
+                    .leq(cut.len() as i32 - 1),
+            );
+        }
+
+        let mut model = vars.minimise(objective).using(default_solver);
+        if let Some(remaining) = deadline.remaining() {
+            model.set_parameter("sec", &remaining.as_secs_f64().to_string());
+        }
+        for constr in constraints {
+            model.add_constraint(constr);
+        }
+
+        match model.solve() {
+            Ok(solution) => Ok(Outcome::Solved(
+                (0..self.asgs.len())
+                    .filter(|&i| solution.value(indexed_vars[i]).round() == 1.0)
+                    .collect(),
+            )),
+            Err(good_lp::ResolutionError::Infeasible) => Ok(Outcome::Infeasible),
+            Err(good_lp::ResolutionError::Other("Stopped")) if deadline.expired() => {
+                Ok(Outcome::TimedOut)
+            }
+            Err(e) => Err(anyhow!("MIP solver failed: {}", e)),
+        }
+    }
+
+    fn write_lp<W: Write>(&self, w: &mut W, header: &[String]) -> Result<()> {
+        for line in header {
+            writeln!(w, "\\ {}", line)?;
+        }
+        match self.names.first() {
+            Some(name) => writeln!(w, "Minimize\n obj: 0 {}\nSubject To", name)?,
+            None => writeln!(w, "Minimize\n obj:\nSubject To")?,
+        }
+        // an area without assignments makes the model infeasible
+        let dummy = self.area_vars.iter().any(|vars| vars.is_empty());
+        for (a, vars) in self.area_vars.iter().enumerate() {
+            write!(w, " area{}:", a)?;
+            if vars.is_empty() {
+                write!(w, " 0 {}", DUMMY)?;
+            }
+            for (k, &i) in vars.iter().enumerate() {
+                if k > 0 && k % 8 == 0 {
+                    write!(w, "\n  ")?;
+                }
+                if k > 0 {
+                    write!(w, " +")?;
+                }
+                write!(w, " {}", self.names[i])?;
+            }
+            writeln!(w, " = 1")?;
+        }
+        for (k, &(i, j)) in self.conflicts.iter().enumerate() {
+            writeln!(
+                w,
+                " conflict{}: {} + {} <= 1",
+                k, self.names[i], self.names[j]
+            )?;
+        }
+        writeln!(w, "Binary")?;
+        for name in &self.names {
+            writeln!(w, " {}", name)?;
+        }
+        if dummy {
+            writeln!(w, " {}", DUMMY)?;
+        }
+        writeln!(w, "End")?;
+        Ok(())
+    }
+
+    /// Writes the model in free MPS format.
+    fn write_mps<W: Write>(&self, w: &mut W, header: &[String]) -> Result<()> {
+        for line in header {
+            writeln!(w, "* {}", line)?;
+        }
+        let mut rows: Vec<Vec<String>> = vec![vec![]; self.asgs.len()];
+        for (a, vars) in self.area_vars.iter().enumerate() {
+            for &i in vars {
+                rows[i].push(format!("area{}", a));
+            }
+        }
+        for (k, &(i, j)) in self.conflicts.iter().enumerate() {
+            rows[i].push(format!("conflict{}", k));
+            rows[j].push(format!("conflict{}", k));
+        }
+
+        writeln!(w, "NAME kenken\nROWS\n N obj")?;
+        for a in 0..self.area_vars.len() {
+            writeln!(w, " E area{}", a)?;
+        }
+        for k in 0..self.conflicts.len() {
+            writeln!(w, " L conflict{}", k)?;
+        }
+        writeln!(w, "COLUMNS\n    MARKER 'MARKER' 'INTORG'")?;
+        for (name, rows) in self.names.iter().zip(&rows) {
+            for row in rows {
+                writeln!(w, "    {} {} 1", name, row)?;
+            }
+        }
+        writeln!(w, "    MARKER 'MARKER' 'INTEND'\nRHS")?;
+        for a in 0..self.area_vars.len() {
+            writeln!(w, "    rhs area{} 1", a)?;
+        }
+        for k in 0..self.conflicts.len() {
+            writeln!(w, "    rhs conflict{} 1", k)?;
+        }
+        writeln!(w, "BOUNDS")?;
+        for name in &self.names {
+            writeln!(w, " BV bnd {}", name)?;
+        }
+        writeln!(w, "ENDATA")?;
+        Ok(())
     }
 
-    let solution = model.solve().unwrap();
+    fn stats(&self) -> Stats {
+        Stats {
+            assignments: self.asgs.len(),
+            variables: self.asgs.len(),
+            constraints: self.area_vars.len() + self.conflicts.len(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Solver for MipSolver {
+    fn name(&self) -> &'static str {
+        "mip"
+    }
 
-    let sol_asgs = asgs
-        .into_iter()
-        .enumerate()
-        .filter(|(i, _)| solution.eval(indexed_vars[*i]).eq(&1.0))
-        .map(|(_, asg)| asg)
-        .collect();
+    fn solutions(
+        &self,
+        kenken: &KenKen,
+        limit: usize,
+    ) -> Result<(Outcome<Vec<Vec<Assignment>>>, Stats)> {
+        let start = Instant::now();
+        let deadline = Deadline::after(self.timeout);
+        let model = MipModel::new(kenken);
+        let mut stats = model.stats();
 
-    sol_asgs
+        let mut cuts = Vec::new();
+        let mut timed_out = false;
+        while cuts.len() < limit {
+            stats.nodes += 1;
+            match model.solve(&cuts, &deadline)? {
+                Outcome::Solved(chosen) => cuts.push(chosen),
+                Outcome::Infeasible => break,
+                Outcome::TimedOut => {
+                    timed_out = true;
+                    break;
+                }
+            }
+        }
+        stats.constraints += cuts.len();
+        stats.elapsed = start.elapsed();
+
+        if timed_out {
+            return Ok((Outcome::TimedOut, stats));
+        }
+        let found = cuts
+            .into_iter()
+            .map(|chosen| chosen.into_iter().map(|i| model.asgs[i].clone()).collect())
+            .collect();
+        Ok((Outcome::found(found), stats))
+    }
+}
+
+#[cfg(test)]
+mod test_solvers {
+    use super::*;
+    use crate::kenken::{Area, Diagonals, Field, Type};
+
+    fn grid(kenken: &KenKen, asgs: &[Assignment]) -> Vec<i64> {
+        let size = kenken.size;
+        (0..size * size)
+            .map(|i| {
+                let field = Field(i % size, i / size);
+                asgs.iter().find_map(|asg| asg.get(&field)).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_backends_agree() {
+        let kenken: KenKen = ron::from_str(include_str!("../old/kenken43208.ron")).unwrap();
+        let (Outcome::Solved(search), _) = Backend::Search.solver(None).solve(&kenken).unwrap()
+        else {
+            panic!("search found no solution");
+        };
+        let (Outcome::Solved(sat), _) = Backend::Sat.solver(None).solve(&kenken).unwrap() else {
+            panic!("sat found no solution");
+        };
+        assert_eq!(grid(&kenken, &search), grid(&kenken, &sat));
+    }
+
+    #[test]
+    fn test_count() {
+        let kenken: KenKen = ron::from_str(include_str!("../old/kenken43208.ron")).unwrap();
+        for backend in [Backend::Search, Backend::Sat] {
+            let (count, _) = backend.solver(None).count(&kenken, 2).unwrap();
+            assert_eq!(count, Outcome::Solved(1));
+        }
+    }
+
+    /// A 4x4 puzzle whose areas are the rows, summing to 10, which leaves every
+    /// Latin square.
+    fn rows_puzzle(diagonals: Diagonals, givens: Vec<(Field, i64)>) -> KenKen {
+        let areas = (0..4)
+            .map(|y| Area::new(Type::Add, 10, (0..4).map(|x| Field(x, y)).collect()))
+            .collect();
+        KenKen {
+            diagonals,
+            givens,
+            ..KenKen::new(0, 4, areas)
+        }
+    }
+
+    #[test]
+    fn test_diagonals() {
+        // 48 of the 576 Latin squares are distinct on both diagonals
+        let kenken = rows_puzzle(Diagonals::Both, vec![]);
+        for backend in [Backend::Search, Backend::Sat] {
+            let (count, _) = backend.solver(None).count(&kenken, 100).unwrap();
+            assert_eq!(count, Outcome::Solved(48));
+        }
+    }
+
+    #[test]
+    fn test_givens() {
+        // fixing the first row leaves 576 / 4! Latin squares
+        let givens = (0..4).map(|x| (Field(x, 0), x as i64 + 1)).collect();
+        let kenken = rows_puzzle(Diagonals::None, givens);
+        for backend in [Backend::Search, Backend::Sat] {
+            let (count, _) = backend.solver(None).count(&kenken, 100).unwrap();
+            assert_eq!(count, Outcome::Solved(24));
+        }
+    }
+
+    #[test]
+    fn test_regions() {
+        // with the 2x2 boxes as regions, the 288 Sudokus of size 4 remain
+        let mut kenken = rows_puzzle(Diagonals::None, vec![]);
+        kenken.regions = [(0, 0), (2, 0), (0, 2), (2, 2)]
+            .into_iter()
+            .map(|(x, y)| {
+                vec![
+                    Field(x, y),
+                    Field(x + 1, y),
+                    Field(x, y + 1),
+                    Field(x + 1, y + 1),
+                ]
+            })
+            .collect();
+        for backend in [Backend::Search, Backend::Sat] {
+            let (count, _) = backend.solver(None).count(&kenken, 1000).unwrap();
+            assert_eq!(count, Outcome::Solved(288));
+        }
+    }
+
+    #[test]
+    fn test_write_lp() {
+        let lp = |kenken: &KenKen| {
+            let mut out = vec![];
+            MipModel::new(kenken).write_lp(&mut out, &[]).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let mut kenken = KenKen {
+            values: Some(vec![-3, 0, 2]),
+            ..KenKen::new(
+                0,
+                3,
+                vec![Area::new(Type::Add, -1, vec![Field(0, 0), Field(1, 0)])],
+            )
+        };
+        let output = lp(&kenken);
+        assert!(
+            output.contains(" area0: a0_m3_2 + a0_2_m3 = 1"),
+            "{}",
+            output
+        );
+        // no minus signs in the variable names
+        assert!(!output.lines().any(|line| line.contains('-')));
+
+        // an area without assignments, in a model without variables
+        kenken.areas[0].solution = 100;
+        let output = lp(&kenken);
+        assert!(output.contains(" obj:\n"));
+        assert!(output.contains(" area0: 0 a_dummy = 1"));
+        assert!(output.contains("Binary\n a_dummy\n"));
+    }
 }
@@ -0,0 +1,183 @@
+use anyhow::{bail, Result};
+use clap::ArgEnum;
+
+use crate::{
+    asg::Assignment,
+    kenken::{Area, Diagonals, Field, KenKen, Symmetry, Type},
+};
+
+/// A symmetry of the grid other than the identity, which turns a puzzle
+/// into an equivalent one.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, ArgEnum)]
+pub enum Transform {
+    /// Quarter turn clockwise.
+    Rot90,
+    Rot180,
+    /// Quarter turn counterclockwise.
+    Rot270,
+    /// Left to right.
+    Mirror,
+    /// Top to bottom.
+    Flip,
+    /// Along the main diagonal.
+    Transpose,
+    /// Along the anti-diagonal.
+    AntiTranspose,
+}
+
+impl Transform {
+    pub fn field(&self, field: &Field, size: u16) -> Field {
+        let n = size - 1;
+        let Field(x, y) = *field;
+        match self {
+            Transform::Rot90 => Field(n - y, x),
+            Transform::Rot180 => Field(n - x, n - y),
+            Transform::Rot270 => Field(y, n - x),
+            Transform::Mirror => Field(n - x, y),
+            Transform::Flip => Field(x, n - y),
+            Transform::Transpose => Field(y, x),
+            Transform::AntiTranspose => Field(n - y, n - x),
+        }
+    }
+
+    fn fields(&self, fields: &[Field], size: u16) -> Vec<Field> {
+        fields.iter().map(|f| self.field(f, size)).collect()
+    }
+
+    /// Whether the main diagonal becomes the anti-diagonal.
+    fn swaps_diagonals(&self) -> bool {
+        matches!(
+            self,
+            Transform::Rot90 | Transform::Rot270 | Transform::Mirror | Transform::Flip
+        )
+    }
+
+    /// The solution of the transformed puzzle for a solution of the original.
+    pub fn assignment(&self, asg: &Assignment, size: u16) -> Assignment {
+        let mut image = Assignment::empty();
+        for (f, v) in asg.iter() {
+            image.set(self.field(&f, size), v);
+        }
+        image
+    }
+}
+
+impl KenKen {
+    /// The same puzzle with every field moved by `transform`. Fails if the
+    /// digits of a `Concat` area would no longer be in reading order.
+    pub fn transformed(&self, transform: Transform) -> Result<KenKen> {
+        let size = self.size;
+        let mut areas = vec![];
+        for area in &self.areas {
+            let moved = Area::new(area.ty, area.solution, transform.fields(&area.fields, size));
+            if area.ty == Type::Concat
+                && moved.reading_order() != transform.fields(&area.reading_order(), size)
+            {
+                bail!(
+                    "{:?} changes the reading order of the Concat area with target {}",
+                    transform,
+                    area.solution
+                );
+            }
+            areas.push(moved);
+        }
+
+        let diagonals = match self.diagonals {
+            Diagonals::Main if transform.swaps_diagonals() => Diagonals::Anti,
+            Diagonals::Anti if transform.swaps_diagonals() => Diagonals::Main,
+            diagonals => diagonals,
+        };
+
+        let mut kenken = KenKen {
+            id: self.id,
+            areas,
+            size,
+            hidden_ops: self.hidden_ops,
+            values: self.values.clone(),
+            diagonals,
+            givens: self
+                .givens
+                .iter()
+                .map(|(f, v)| (transform.field(f, size), *v))
+                .collect(),
+            regions: self
+                .regions
+                .iter()
+                .map(|region| transform.fields(region, size))
+                .collect(),
+            killer: self.killer,
+            symmetry: self.symmetry,
+        };
+        // the mirror axis turns with the grid, rotations stay rotations
+        if kenken.symmetry == Symmetry::Mirror && !kenken.symmetric_layout() {
+            kenken.symmetry = Symmetry::None;
+        }
+        Ok(kenken)
+    }
+}
+
+#[cfg(test)]
+mod test_transform {
+    use super::*;
+    use crate::solve::{Backend, Outcome};
+
+    fn solution(kenken: &KenKen, backend: Backend) -> Assignment {
+        let (Outcome::Solved(asgs), _) = backend.solver(None).solve(kenken).unwrap() else {
+            panic!("no solution");
+        };
+        let mut sol = Assignment::empty();
+        for (f, v) in asgs.iter().flat_map(|asg| asg.iter()) {
+            sol.set(f, v);
+        }
+        sol
+    }
+
+    #[test]
+    fn test_solvers_invariant() {
+        let kenken: KenKen = ron::from_str(include_str!("../old/kenken43208.ron")).unwrap();
+        let sol = solution(&kenken, Backend::Search);
+        for transform in Transform::value_variants() {
+            let image = kenken.transformed(*transform).unwrap();
+            for backend in [Backend::Search, Backend::Sat] {
+                assert_eq!(
+                    solution(&image, backend),
+                    transform.assignment(&sol, kenken.size),
+                    "{:?}",
+                    transform
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_transforms() {
+        let size = 4;
+        let f = Field(1, 0);
+        assert_eq!(
+            Transform::Rot90.field(&Transform::Rot270.field(&f, size), size),
+            f
+        );
+        assert_eq!(
+            Transform::Rot90.field(&Transform::Rot90.field(&f, size), size),
+            Transform::Rot180.field(&f, size)
+        );
+        // a transposition is a mirror after a quarter turn
+        assert_eq!(
+            Transform::Mirror.field(&Transform::Rot90.field(&f, size), size),
+            Transform::Transpose.field(&f, size)
+        );
+        assert_eq!(
+            Transform::Flip.field(&Transform::Rot90.field(&f, size), size),
+            Transform::AntiTranspose.field(&f, size)
+        );
+        for transform in Transform::value_variants() {
+            let main = transform.field(&Field(0, 0), size);
+            assert_eq!(
+                main.0 != main.1,
+                transform.swaps_diagonals(),
+                "{:?}",
+                transform
+            );
+        }
+    }
+}
@@ -1,4 +1,9 @@
-use crate::kenken::{Area, KenKen};
+use std::fmt::Display;
+
+use crate::{
+    asg::Assignment,
+    kenken::{connected, Area, Field, KenKen, Symmetry, Type},
+};
 
 pub trait Validator {
     fn validate(&self) -> ValidationResult;
@@ -6,17 +11,223 @@ pub trait Validator {
 
 impl Validator for KenKen {
     fn validate(&self) -> ValidationResult {
+        let mut errors: Vec<ValidationError> = self
+            .areas
+            .iter()
+            .filter_map(|area| area.validate().err())
+            .flatten()
+            .collect();
+        let values = self.values();
+        for &(field, value) in &self.givens {
+            if field.0 >= self.size || field.1 >= self.size || !values.contains(&value) {
+                errors.push(ValidationError::Given { field, value });
+            }
+        }
+        if !self.regions.is_empty() && !self.valid_regions() {
+            errors.push(ValidationError::Regions {
+                regions: self.regions.clone(),
+            });
+        }
+        if !self.symmetric_layout() {
+            errors.push(ValidationError::Symmetry {
+                symmetry: self.symmetry,
+            });
+        }
+        if let Err(error) = self.validate_values() {
+            errors.push(error);
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl KenKen {
+    /// The value set must have one distinct value per column. The solvers rely
+    /// on it, so it is also checked whenever a puzzle is read.
+    pub fn validate_values(&self) -> Result<(), ValidationError> {
+        if let Some(values) = &self.values {
+            let mut distinct = values.clone();
+            distinct.sort_unstable();
+            distinct.dedup();
+            if distinct.len() != values.len() || values.len() != self.size as usize {
+                return Err(ValidationError::ValueSet {
+                    size: self.size,
+                    values: values.clone(),
+                });
+            }
+        }
         Ok(())
     }
+
+    /// Checks a filled grid, e.g. read from an external solver: every value
+    /// must be in the value set and once in each house, and every area must
+    /// hold one of its possible assignments.
+    pub fn validate_solution(&self, grid: &Assignment) -> ValidationResult {
+        let mut errors = vec![];
+        let values = self.values();
+        for (field, value) in grid.iter() {
+            if !values.contains(&value) {
+                errors.push(ValidationError::Value { field, value });
+            }
+        }
+        for house in self.houses() {
+            for (i, f) in house.iter().enumerate() {
+                if let Some(g) = house[..i]
+                    .iter()
+                    .find(|g| grid.get(g).is_some() && grid.get(g) == grid.get(f))
+                {
+                    errors.push(ValidationError::Repeated {
+                        value: grid.get(f).unwrap(),
+                        fields: (*g, *f),
+                    });
+                }
+            }
+        }
+        for area in &self.areas {
+            if !self
+                .possible_assignments(area)
+                .contains(&grid.restrict(&area.fields))
+            {
+                errors.push(ValidationError::Target {
+                    ty: area.ty,
+                    solution: area.solution,
+                    fields: area.fields.clone(),
+                });
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// The regions must partition the grid into `size` connected regions of
+    /// `size` fields.
+    fn valid_regions(&self) -> bool {
+        let n = self.size as usize;
+        let mut fields: Vec<Field> = self.regions.iter().flatten().cloned().collect();
+        fields.sort();
+        fields.dedup();
+        self.regions.len() == n
+            && fields.len() == n * n
+            && fields.iter().all(|f| f.0 < self.size && f.1 < self.size)
+            && self
+                .regions
+                .iter()
+                .all(|region| region.len() == n && connected(region))
+    }
 }
 
 impl Area {
     fn validate(&self) -> ValidationResult {
-        Ok(())
+        let valid_count = match self.ty {
+            Type::Single => self.fields.len() == 1,
+            Type::Mod => self.fields.len() == 2,
+            Type::Sub
+            | Type::Div
+            | Type::Min
+            | Type::Max
+            | Type::Gcd
+            | Type::Lcm
+            | Type::Concat => self.fields.len() >= 2,
+            Type::Add | Type::Mul => !self.fields.is_empty(),
+        };
+        if valid_count {
+            Ok(())
+        } else {
+            Err(vec![ValidationError::FieldCount {
+                ty: self.ty,
+                fields: self.fields.clone(),
+            }])
+        }
     }
 }
 
 type ValidationResult = Result<(), Vec<ValidationError>>;
 
 #[derive(Debug, Clone)]
-pub enum ValidationError {}
+pub enum ValidationError {
+    /// The operator of the area does not allow this many fields.
+    FieldCount { ty: Type, fields: Vec<Field> },
+    /// The value set does not consist of one distinct value per column.
+    ValueSet { size: u16, values: Vec<i64> },
+    /// The given lies outside the grid or is not in the value set.
+    Given { field: Field, value: i64 },
+    /// The regions do not split the grid into `size` connected regions of
+    /// `size` fields.
+    Regions { regions: Vec<Vec<Field>> },
+    /// The area layout lacks the symmetry it declares.
+    Symmetry { symmetry: Symmetry },
+    /// A value of a solution is not in the value set.
+    Value { field: Field, value: i64 },
+    /// A value of a solution appears twice in a house.
+    Repeated { value: i64, fields: (Field, Field) },
+    /// The values of a solution in an area do not reach its target.
+    Target {
+        ty: Type,
+        solution: i64,
+        fields: Vec<Field>,
+    },
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::FieldCount { ty, fields } => {
+                write!(f, "{:?} area with {} fields:", ty, fields.len())?;
+                for field in fields {
+                    write!(f, " ({},{})", field.0, field.1)?;
+                }
+                Ok(())
+            }
+            ValidationError::ValueSet { size, values } => {
+                write!(f, "value set {:?} for a grid of size {}", values, size)
+            }
+            ValidationError::Given { field, value } => {
+                write!(f, "given {} at ({},{})", value, field.0, field.1)
+            }
+            ValidationError::Symmetry { symmetry } => {
+                write!(f, "area layout without {:?} symmetry", symmetry)
+            }
+            ValidationError::Value { field, value } => {
+                write!(
+                    f,
+                    "value {} at ({},{}) outside the value set",
+                    value, field.0, field.1
+                )
+            }
+            ValidationError::Repeated {
+                value,
+                fields: (a, b),
+            } => {
+                write!(
+                    f,
+                    "value {} at ({},{}) and ({},{}) in the same house",
+                    value, a.0, a.1, b.0, b.1
+                )
+            }
+            ValidationError::Target {
+                ty,
+                solution,
+                fields,
+            } => {
+                write!(f, "{:?} area with target {} not satisfied:", ty, solution)?;
+                for field in fields {
+                    write!(f, " ({},{})", field.0, field.1)?;
+                }
+                Ok(())
+            }
+            ValidationError::Regions { regions } => {
+                write!(f, "{} regions of sizes", regions.len())?;
+                for region in regions {
+                    write!(f, " {}", region.len())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}